@@ -0,0 +1,41 @@
+//! Feeds arbitrary headers (and therefore arbitrary digests) to the seal verification, with the
+//! leader election mode, epoch randomness, parent timestamp and current time taken from the input
+//! so that runs are reproducible.
+
+#![no_main]
+
+use codec::Decode;
+use consensus_primitives::{sr25519::AuthorityPair, LeaderElection, Randomness};
+use lazy_static::lazy_static;
+use libfuzzer_sys::fuzz_target;
+use sp_core::Pair;
+use substrate_test_runtime_client::runtime::{Block, Header};
+
+lazy_static! {
+    // the block authority followed by a backup authority
+    static ref AUTHORITIES: Vec<<AuthorityPair as Pair>::Public> = ["//Alice", "//Charlie"]
+        .iter()
+        .map(|seed| {
+            AuthorityPair::from_string(seed, None)
+                .expect("static values are valid; qed")
+                .public()
+        })
+        .collect();
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (leader_election, randomness, parent_timestamp, now, mut header) =
+        match <(LeaderElection, Randomness, u64, u64, Header)>::decode(&mut &data[..]) {
+            Ok(input) => input,
+            Err(_) => return,
+        };
+
+    let _ = consensus::fuzzing::check_header::<Block, AuthorityPair>(
+        AUTHORITIES.clone(),
+        leader_election,
+        &randomness,
+        &mut header,
+        parent_timestamp,
+        now,
+    );
+});
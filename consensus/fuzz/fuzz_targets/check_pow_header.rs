@@ -0,0 +1,24 @@
+//! Feeds arbitrary headers to the proof of work seal verification, with the difficulty, parent
+//! timestamp and current time taken from the input.
+
+#![no_main]
+
+use codec::Decode;
+use consensus_primitives::sr25519::AuthorityPair;
+use libfuzzer_sys::fuzz_target;
+use substrate_test_runtime_client::runtime::{Block, Header};
+
+fuzz_target!(|data: &[u8]| {
+    let (difficulty, parent_timestamp, now, mut header) =
+        match <(u128, u64, u64, Header)>::decode(&mut &data[..]) {
+            Ok(input) => input,
+            Err(_) => return,
+        };
+
+    let _ = consensus::fuzzing::check_pow_header::<Block, AuthorityPair>(
+        difficulty,
+        &mut header,
+        parent_timestamp,
+        now,
+    );
+});
@@ -0,0 +1,41 @@
+//! Feeds arbitrary singleton pre-runtime digests to the VRF leader election check, optionally
+//! replacing the VRF with a valid one by the block authority so that the slot and threshold
+//! checks are exercised too.
+
+#![no_main]
+
+use codec::{Decode, Encode};
+use consensus_primitives::{sr25519::AuthorityPair, PreDigest, Randomness};
+use lazy_static::lazy_static;
+use libfuzzer_sys::fuzz_target;
+use sp_core::Pair;
+use substrate_test_runtime_client::runtime::Block;
+
+lazy_static! {
+    static ref AUTHORITY: AuthorityPair =
+        AuthorityPair::from_string("//Alice", None).expect("static values are valid; qed");
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (randomness, parent_timestamp, sign, pre_digest) =
+        match <(Randomness, u64, bool, Vec<u8>)>::decode(&mut &data[..]) {
+            Ok(input) => input,
+            Err(_) => return,
+        };
+
+    let pre_digest = match PreDigest::decode(&mut &pre_digest[..]) {
+        Ok(mut pre_digest) if sign => {
+            pre_digest.vrf =
+                consensus::fuzzing::vrf_sign(&*AUTHORITY, &randomness, pre_digest.timestamp);
+            pre_digest.encode()
+        }
+        _ => pre_digest,
+    };
+
+    let _ = consensus::fuzzing::check_vrf::<Block, AuthorityPair>(
+        vec![AUTHORITY.public()],
+        &randomness,
+        &pre_digest,
+        parent_timestamp,
+    );
+});
@@ -0,0 +1,16 @@
+//! Feeds arbitrary bytes to the decoding and verification of gossiped finality messages.
+
+#![no_main]
+
+use consensus_primitives::sr25519::AuthorityPair;
+use libfuzzer_sys::fuzz_target;
+use sp_core::Pair;
+use substrate_test_runtime_client::runtime::Hash;
+
+fuzz_target!(|data: &[u8]| {
+    let authority = AuthorityPair::from_string("//Bob", None)
+        .expect("static values are valid; qed")
+        .public();
+
+    let _ = consensus::fuzzing::check_finality_message::<Hash, AuthorityPair>(authority, data);
+});
@@ -0,0 +1,46 @@
+//! Imports arbitrary headers with arbitrary justifications attached, optionally replacing the
+//! justification with a valid signature by the finality authority so that the ancestry checks
+//! are exercised too. Blocks are imported without a post hash set, into a fresh client for every
+//! input so that inputs don't depend on blocks imported or finalized by earlier ones.
+
+#![no_main]
+
+use std::sync::Arc;
+
+use codec::{Decode, Encode};
+use consensus_primitives::sr25519::AuthorityPair;
+use lazy_static::lazy_static;
+use libfuzzer_sys::fuzz_target;
+use sp_consensus::{BlockImportParams, BlockOrigin};
+use sp_core::Pair;
+use sp_runtime::traits::Header as _;
+use substrate_test_runtime_client::runtime::{Block, Header};
+
+lazy_static! {
+    static ref FINALITY_KEY: AuthorityPair =
+        AuthorityPair::from_string("//Bob", None).expect("static values are valid; qed");
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (header, justification, sign) = match <(Header, Vec<u8>, bool)>::decode(&mut &data[..]) {
+        Ok(input) => input,
+        Err(_) => return,
+    };
+
+    let justification = if sign {
+        FINALITY_KEY.sign(header.hash().as_ref()).encode()
+    } else {
+        justification
+    };
+
+    let client = Arc::new(substrate_test_runtime_client::new());
+    let mut block = BlockImportParams::new(BlockOrigin::NetworkBroadcast, header);
+    block.justification = Some(justification);
+
+    let _ = consensus::fuzzing::import_block::<Block, _, _, AuthorityPair>(
+        client.clone(),
+        client,
+        FINALITY_KEY.public(),
+        block,
+    );
+});
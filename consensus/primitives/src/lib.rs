@@ -0,0 +1,262 @@
+//! Primitives for the singleton consensus engine, shared between the node and the runtime.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Codec, Decode, Encode};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_inherents::InherentIdentifier;
+use sp_runtime::{ConsensusEngineId, KeyTypeId, RuntimeDebug};
+use sp_std::vec::Vec;
+
+pub use sp_consensus_vrf::schnorrkel::{Randomness, VRFOutput, VRFProof};
+
+pub const SINGLETON_ENGINE_ID: ConsensusEngineId = *b"SGTN";
+
+/// Engine id of the pre-runtime digest and seal of blocks sealed with proof of work instead of an
+/// authority signature.
+pub const SINGLETON_POW_ENGINE_ID: ConsensusEngineId = *b"SGTW";
+
+/// Key type for singleton authority keys.
+pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"sgtn");
+
+/// Key type for singleton finality authority keys, kept apart from block authority keys so that
+/// both can be part of the same set of session keys.
+pub const FINALITY_KEY_TYPE: KeyTypeId = KeyTypeId(*b"sgtf");
+
+/// Identifier of the inherent data carrying the number of the last finalized block known to the
+/// block author and its singleton finality justification.
+pub const FINALITY_INHERENT_IDENTIFIER: InherentIdentifier = *b"sgtnfnlt";
+
+/// Block time in milliseconds of networks whose genesis doesn't set another one.
+pub const DEFAULT_BLOCK_TIME: u64 = 10_000;
+
+/// Index of a block authority in the list of authorities kept by the runtime.
+pub type AuthorityIndex = u32;
+
+/// Pre-runtime digest added by the block author, identifying it to the runtime and to the
+/// verifier before the seal is checked.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub struct PreDigest {
+    pub authority_index: AuthorityIndex,
+    /// Unix time in milliseconds at which the block was authored.
+    pub timestamp: u64,
+    /// Proof that the author was elected for the slot of `timestamp`, with VRF leader election.
+    pub vrf: Option<VrfSignature>,
+}
+
+/// Pre-runtime digest added by authors sealing blocks with proof of work. These blocks have no
+/// `PreDigest`, so the runtime finds no author for them.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub struct PowPreDigest {
+    /// Unix time in milliseconds at which the block was authored.
+    pub timestamp: u64,
+}
+
+impl PowPreDigest {
+    /// Finds and decodes the proof of work pre-runtime digest among the given digest items.
+    pub fn find<'a, I>(digests: I) -> Option<PowPreDigest>
+    where
+        I: IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+    {
+        digests
+            .into_iter()
+            .find(|(id, _)| *id == SINGLETON_POW_ENGINE_ID)
+            .and_then(|(_, mut data)| PowPreDigest::decode(&mut data).ok())
+    }
+}
+
+/// Output and proof of the leader election VRF, evaluated over the epoch randomness and the slot
+/// of the block.
+#[derive(Clone, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub struct VrfSignature {
+    pub output: VRFOutput,
+    pub proof: VRFProof,
+}
+
+/// Handler notified by the runtime when it learns from a finality justification that blocks up to
+/// the given number are finalized.
+pub trait OnFinality<BlockNumber> {
+    fn on_finality(number: BlockNumber);
+
+    /// Returns the weight of `on_finality` for blocks up to `number`, the ones up to
+    /// `last_finalized` having been noted already.
+    fn on_finality_weight(number: BlockNumber, last_finalized: BlockNumber) -> u64;
+}
+
+impl<BlockNumber> OnFinality<BlockNumber> for () {
+    fn on_finality(_number: BlockNumber) {}
+
+    fn on_finality_weight(_number: BlockNumber, _last_finalized: BlockNumber) -> u64 {
+        0
+    }
+}
+
+/// How the authority allowed to seal a block is chosen.
+#[derive(Clone, Copy, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Deserialize, Serialize))]
+pub enum LeaderElection {
+    /// The block authority seals all blocks, and backup authorities take over in order when it
+    /// stops doing so.
+    Fixed,
+    /// Each authority may seal a block in a slot if its sr25519 VRF output over the epoch
+    /// randomness and the slot is below a threshold.
+    Vrf,
+}
+
+impl Default for LeaderElection {
+    fn default() -> Self {
+        LeaderElection::Fixed
+    }
+}
+
+impl PreDigest {
+    /// Finds and decodes the singleton pre-runtime digest among the given digest items.
+    pub fn find<'a, I>(digests: I) -> Option<PreDigest>
+    where
+        I: IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+    {
+        digests
+            .into_iter()
+            .find(|(id, _)| *id == SINGLETON_ENGINE_ID)
+            .and_then(|(_, mut data)| PreDigest::decode(&mut data).ok())
+    }
+}
+
+/// The signature scheme used for singleton seals and finality justifications.
+#[derive(Clone, Copy, Decode, Encode, Eq, PartialEq, RuntimeDebug)]
+pub enum SignatureScheme {
+    Sr25519,
+    Ed25519,
+    Ecdsa,
+}
+
+pub mod sr25519 {
+    mod app {
+        use sp_application_crypto::{app_crypto, sr25519};
+        app_crypto!(sr25519, super::super::KEY_TYPE);
+    }
+
+    mod finality_app {
+        use sp_application_crypto::{app_crypto, sr25519};
+        app_crypto!(sr25519, super::super::FINALITY_KEY_TYPE);
+    }
+
+    pub const SIGNATURE_SCHEME: super::SignatureScheme = super::SignatureScheme::Sr25519;
+
+    /// A singleton authority keypair using sr25519 as its crypto.
+    #[cfg(feature = "std")]
+    pub type AuthorityPair = app::Pair;
+
+    /// A singleton authority signature using sr25519 as its crypto.
+    pub type AuthoritySignature = app::Signature;
+
+    /// A singleton authority identifier using sr25519 as its crypto.
+    pub type AuthorityId = app::Public;
+
+    /// A singleton finality authority keypair using sr25519 as its crypto.
+    #[cfg(feature = "std")]
+    pub type FinalityPair = finality_app::Pair;
+
+    /// A singleton finality authority identifier using sr25519 as its crypto.
+    pub type FinalityId = finality_app::Public;
+}
+
+pub mod ed25519 {
+    mod app {
+        use sp_application_crypto::{app_crypto, ed25519};
+        app_crypto!(ed25519, super::super::KEY_TYPE);
+    }
+
+    mod finality_app {
+        use sp_application_crypto::{app_crypto, ed25519};
+        app_crypto!(ed25519, super::super::FINALITY_KEY_TYPE);
+    }
+
+    pub const SIGNATURE_SCHEME: super::SignatureScheme = super::SignatureScheme::Ed25519;
+
+    /// A singleton authority keypair using ed25519 as its crypto.
+    #[cfg(feature = "std")]
+    pub type AuthorityPair = app::Pair;
+
+    /// A singleton authority signature using ed25519 as its crypto.
+    pub type AuthoritySignature = app::Signature;
+
+    /// A singleton authority identifier using ed25519 as its crypto.
+    pub type AuthorityId = app::Public;
+
+    /// A singleton finality authority keypair using ed25519 as its crypto.
+    #[cfg(feature = "std")]
+    pub type FinalityPair = finality_app::Pair;
+
+    /// A singleton finality authority identifier using ed25519 as its crypto.
+    pub type FinalityId = finality_app::Public;
+}
+
+pub mod ecdsa {
+    mod app {
+        use sp_application_crypto::{app_crypto, ecdsa};
+        app_crypto!(ecdsa, super::super::KEY_TYPE);
+    }
+
+    mod finality_app {
+        use sp_application_crypto::{app_crypto, ecdsa};
+        app_crypto!(ecdsa, super::super::FINALITY_KEY_TYPE);
+    }
+
+    pub const SIGNATURE_SCHEME: super::SignatureScheme = super::SignatureScheme::Ecdsa;
+
+    /// A singleton authority keypair using ecdsa as its crypto.
+    #[cfg(feature = "std")]
+    pub type AuthorityPair = app::Pair;
+
+    /// A singleton authority signature using ecdsa as its crypto.
+    pub type AuthoritySignature = app::Signature;
+
+    /// A singleton authority identifier using ecdsa as its crypto.
+    pub type AuthorityId = app::Public;
+
+    /// A singleton finality authority keypair using ecdsa as its crypto.
+    #[cfg(feature = "std")]
+    pub type FinalityPair = finality_app::Pair;
+
+    /// A singleton finality authority identifier using ecdsa as its crypto.
+    pub type FinalityId = finality_app::Public;
+}
+
+sp_api::decl_runtime_apis! {
+    /// API necessary for block authorship and finality with the singleton consensus engine.
+    pub trait SingletonApi<AuthorityId: Codec> {
+        /// Returns the signature scheme used for seals and finality justifications.
+        fn signature_scheme() -> SignatureScheme;
+
+        /// Returns how the authority allowed to seal a block is chosen.
+        fn leader_election() -> LeaderElection;
+
+        /// Returns the time between blocks in milliseconds, which is also the length of the
+        /// slots blocks are timestamped in.
+        fn block_time() -> u64;
+
+        /// Returns the randomness of the current epoch, used for VRF leader election.
+        fn epoch_randomness() -> Randomness;
+
+        /// Returns the difficulty of proof of work seals, if anyone may seal blocks with proof of
+        /// work besides the block authorities. A sealed header's Blake2 hash must be at most
+        /// `U256::MAX / difficulty`.
+        fn pow_difficulty() -> Option<u128>;
+
+        /// Returns the block authorities, indexed by `PreDigest::authority_index`.
+        fn authorities() -> Vec<AuthorityId>;
+
+        /// Returns the finality authority, if any. The consensus engine signs seals and finality
+        /// justifications with the same key pair type, so the key is returned as an
+        /// `AuthorityId`.
+        fn finality_authority() -> Option<AuthorityId>;
+
+        /// Returns the block authorities queued to take over at the next session.
+        fn next_authorities() -> Vec<AuthorityId>;
+
+        /// Returns the finality authority queued to take over at the next session, if any.
+        fn next_finality_authority() -> Option<AuthorityId>;
+    }
+}
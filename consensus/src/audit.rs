@@ -0,0 +1,347 @@
+//! Offline verification and inspection of the seals and justifications of the local chain.
+
+use std::collections::BTreeMap;
+
+use codec::{Codec, Decode};
+use log::debug;
+use sc_client_api::BlockBackend;
+use sp_api::BlockId;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{
+    generic::DigestItem,
+    traits::{Block as BlockT, Header as HeaderT, NumberFor, One, Zero},
+    ConsensusEngineId,
+};
+
+use crate::{
+    header_timestamp, unix_time_millis, AuthorityIndex, ConfigAt, EpochRandomness, Error,
+    HeaderVerifier, LeaderElection, PowPreDigest, PowSeal, PreDigest, SingletonConfig,
+    SingletonFinalityJustification, SingletonPair, SingletonSeal, SINGLETON_ENGINE_ID,
+    SINGLETON_POW_ENGINE_ID,
+};
+
+/// Summary of the verification of the local chain.
+#[derive(Debug)]
+pub struct ChainVerification<Block: BlockT> {
+    /// Number of blocks checked, excluding the genesis block.
+    pub blocks: u64,
+    /// Number of blocks sealed by each authority, indexed by `PreDigest::authority_index`.
+    pub sealed: BTreeMap<AuthorityIndex, u64>,
+    /// Number of blocks sealed with proof of work.
+    pub mined: u64,
+    /// Number of singleton justifications checked.
+    pub justifications: u64,
+    /// Number of justifications of other finality gadgets, e.g. GRANDPA, which aren't checked.
+    pub foreign_justifications: u64,
+    /// The first block which failed verification, and why. Blocks after it aren't checked.
+    pub first_invalid: Option<(NumberFor<Block>, Block::Hash, Error)>,
+}
+
+/// A block seal, decoded for inspection.
+#[derive(Debug)]
+pub enum InspectedSeal<Signature> {
+    /// Signature of the authority named in the singleton pre-runtime digest.
+    Authority(Signature),
+    /// Nonce of a block sealed with proof of work.
+    Work(u64),
+    /// Seal of another engine, or a singleton seal which can't be decoded.
+    Other(ConsensusEngineId, Vec<u8>),
+}
+
+/// A stored justification, decoded for inspection.
+#[derive(Debug)]
+pub enum InspectedJustification<Signature> {
+    /// Singleton justification, and whether it's signed by the finality authority in effect at
+    /// the parent of its block.
+    Singleton(Signature, bool),
+    /// Justification of another finality gadget, e.g. GRANDPA.
+    Other(Vec<u8>),
+}
+
+/// Singleton consensus details of a block.
+#[derive(Debug)]
+pub struct BlockInspection<Block: BlockT, Public, Signature> {
+    pub number: NumberFor<Block>,
+    /// Hash of the header without its seal, which is what the sealing authority signs.
+    pub pre_hash: Block::Hash,
+    /// Hash of the header including its seal, i.e. the block hash.
+    pub post_hash: Block::Hash,
+    pub seal: Option<InspectedSeal<Signature>>,
+    pub pre_digest: Option<PreDigest>,
+    pub pow_pre_digest: Option<PowPreDigest>,
+    /// The authority named in the pre-runtime digest, among those in effect at the parent block.
+    pub authority: Option<Public>,
+    /// Outcome of verifying the seal the same way as on import. Not set for the genesis block.
+    pub seal_check: Option<Result<(), Error>>,
+    /// Consensus digest items of all engines.
+    pub consensus_logs: Vec<(ConsensusEngineId, Vec<u8>)>,
+    pub justification: Option<InspectedJustification<Signature>>,
+}
+
+/// Decodes the singleton digest items and the justification of the given block, and verifies its
+/// seal and justification against the configuration looked up with `config_at`.
+pub fn inspect_block<Block, Client, P>(
+    client: &Client,
+    at: BlockId<Block>,
+    config_at: &ConfigAt<Block, P>,
+    epoch_randomness: &EpochRandomness<Block>,
+) -> Result<BlockInspection<Block, P::Public, P::Signature>, Error>
+where
+    Block: BlockT,
+    Client: BlockBackend<Block> + HeaderBackend<Block>,
+    P: SingletonPair,
+    P::Public: Codec,
+    P::Signature: Codec,
+{
+    let header = client
+        .header(at)
+        .map_err(Error::Client)?
+        .ok_or_else(|| Error::Client(sp_blockchain::Error::UnknownBlock(format!("{}", at))))?;
+    let post_hash = header.hash();
+
+    let mut unsealed = header.clone();
+    let seal = match unsealed.digest_mut().pop() {
+        Some(DigestItem::Seal(id, data)) => Some(match id {
+            SINGLETON_ENGINE_ID => SingletonSeal::decode(&mut &data[..])
+                .map(|seal| InspectedSeal::Authority(seal.0))
+                .unwrap_or(InspectedSeal::Other(id, data)),
+            SINGLETON_POW_ENGINE_ID => PowSeal::decode(&mut &data[..])
+                .map(|seal| InspectedSeal::Work(seal.nonce))
+                .unwrap_or(InspectedSeal::Other(id, data)),
+            _ => InspectedSeal::Other(id, data),
+        }),
+        Some(item) => {
+            unsealed.digest_mut().push(item);
+            None
+        }
+        None => None,
+    };
+
+    let pre_runtime_digests = || {
+        header
+            .digest()
+            .logs()
+            .iter()
+            .filter_map(|item| item.as_pre_runtime())
+    };
+    let pre_digest = PreDigest::find(pre_runtime_digests());
+    let pow_pre_digest = PowPreDigest::find(pre_runtime_digests());
+
+    let consensus_logs = header
+        .digest()
+        .logs()
+        .iter()
+        .filter_map(|item| match item {
+            DigestItem::Consensus(id, data) => Some((*id, data.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let (authority, seal_check) = if header.number().is_zero() {
+        (None, None)
+    } else {
+        let parent_id = BlockId::Hash(*header.parent_hash());
+        let parent = client
+            .header(parent_id)
+            .map_err(Error::Client)?
+            .ok_or_else(|| {
+                Error::Client(sp_blockchain::Error::UnknownBlock(format!("{}", parent_id)))
+            })?;
+        let config = config_at(&parent_id)?;
+
+        let authority = pre_digest.as_ref().and_then(|pre_digest| {
+            config
+                .block_authorities()
+                .get(pre_digest.authority_index as usize)
+                .map(|authority| authority.as_ref().clone())
+        });
+
+        let seal_check = verify_seal(
+            &config,
+            epoch_randomness,
+            &mut header.clone(),
+            &parent_id,
+            header_timestamp(&parent),
+            unix_time_millis(),
+        )
+        .map(|_| ());
+
+        (authority, Some(seal_check))
+    };
+
+    let justification = match client
+        .justification(&BlockId::Hash(post_hash))
+        .map_err(Error::Client)?
+    {
+        Some(justification) => {
+            match SingletonFinalityJustification::<P::Signature>::decode(&mut &justification[..]) {
+                Ok(justification) => {
+                    // the finality authority in effect at the parent signs the block, as on
+                    // import; the genesis block has no parent.
+                    let at = if header.number().is_zero() {
+                        BlockId::Hash(post_hash)
+                    } else {
+                        BlockId::Hash(*header.parent_hash())
+                    };
+                    let finality_authority = config_at(&at)?.finality_authority;
+
+                    let valid = P::verify(
+                        justification.as_ref(),
+                        &post_hash,
+                        finality_authority.as_ref(),
+                    );
+
+                    Some(InspectedJustification::Singleton(justification.0, valid))
+                }
+                Err(_) => Some(InspectedJustification::Other(justification)),
+            }
+        }
+        None => None,
+    };
+
+    Ok(BlockInspection {
+        number: *header.number(),
+        pre_hash: unsealed.hash(),
+        post_hash,
+        seal,
+        pre_digest,
+        pow_pre_digest,
+        authority,
+        seal_check,
+        consensus_logs,
+        justification,
+    })
+}
+
+/// Checks the seal of every block of the canonical chain, from genesis to the best block, and
+/// every singleton justification stored for them.
+///
+/// Seals are checked the same way as on import, against the authorities in effect at the parent
+/// block, and justifications against the finality authority in effect at the parent block too,
+/// both looked up with `config_at`. With configurations read from the runtime, this requires the
+/// state of every block, i.e. an archive node.
+pub fn verify_chain<Block, Client, P>(
+    client: &Client,
+    config_at: &ConfigAt<Block, P>,
+    epoch_randomness: &EpochRandomness<Block>,
+) -> Result<ChainVerification<Block>, Error>
+where
+    Block: BlockT,
+    Client: BlockBackend<Block> + HeaderBackend<Block>,
+    P: SingletonPair,
+    P::Public: Codec,
+    P::Signature: Codec,
+{
+    let mut verification = ChainVerification {
+        blocks: 0,
+        sealed: BTreeMap::new(),
+        mined: 0,
+        justifications: 0,
+        foreign_justifications: 0,
+        first_invalid: None,
+    };
+
+    let best_number = client.info().best_number;
+    let now = unix_time_millis();
+
+    let mut parent = client
+        .header(BlockId::Number(Zero::zero()))
+        .map_err(Error::Client)?
+        .ok_or_else(|| Error::Client(sp_blockchain::Error::UnknownBlock("genesis".into())))?;
+
+    let mut number = One::one();
+
+    while number <= best_number {
+        let header = client
+            .header(BlockId::Number(number))
+            .map_err(Error::Client)?
+            .ok_or_else(|| {
+                Error::Client(sp_blockchain::Error::UnknownBlock(format!("#{}", number)))
+            })?;
+        let hash = header.hash();
+        let parent_id = BlockId::Hash(parent.hash());
+
+        let config = config_at(&parent_id)?;
+        let parent_timestamp = header_timestamp(&parent);
+
+        match verify_seal(
+            &config,
+            epoch_randomness,
+            &mut header.clone(),
+            &parent_id,
+            parent_timestamp,
+            now,
+        ) {
+            Ok(Some(authority_index)) => {
+                *verification.sealed.entry(authority_index).or_default() += 1;
+            }
+            Ok(None) => verification.mined += 1,
+            Err(Error::Seal(err)) => {
+                verification.first_invalid = Some((number, hash, err.into()));
+                break;
+            }
+            Err(err) => return Err(err),
+        }
+
+        if let Some(justification) = client
+            .justification(&BlockId::Hash(hash))
+            .map_err(Error::Client)?
+        {
+            match SingletonFinalityJustification::<P::Signature>::decode(&mut &justification[..]) {
+                Ok(justification) => {
+                    let finality_authority = config.finality_authority.clone();
+
+                    if !P::verify(justification.as_ref(), &hash, finality_authority.as_ref()) {
+                        let err = Error::BadJustification(format!("#{} ({:?})", number, hash));
+                        verification.first_invalid = Some((number, hash, err));
+                        break;
+                    }
+
+                    verification.justifications += 1;
+                }
+                Err(_) => verification.foreign_justifications += 1,
+            }
+        }
+
+        debug!(target: "singleton", "Verified block #{} ({:?})", number, hash);
+
+        verification.blocks += 1;
+        parent = header;
+        number += One::one();
+    }
+
+    Ok(verification)
+}
+
+/// Checks the seal of the given header, returning the index of the authority which sealed it or
+/// `None` if it was sealed with proof of work. Invalid seals are reported as `Error::Seal`.
+fn verify_seal<Block, P>(
+    config: &SingletonConfig<P>,
+    epoch_randomness: &EpochRandomness<Block>,
+    header: &mut Block::Header,
+    parent_id: &BlockId<Block>,
+    parent_timestamp: u64,
+    now: u64,
+) -> Result<Option<AuthorityIndex>, Error>
+where
+    Block: BlockT,
+    P: SingletonPair,
+    P::Signature: Codec,
+{
+    let verifier = HeaderVerifier::<Block, P>::new(config);
+
+    if let Some(DigestItem::Seal(SINGLETON_POW_ENGINE_ID, _)) = header.digest().logs().last() {
+        verifier.check_pow_header(header, parent_timestamp, now)?;
+        return Ok(None);
+    }
+
+    let (_, pre_digest) = verifier.check_header(header, parent_timestamp, now)?;
+
+    if config.leader_election == LeaderElection::Vrf {
+        let randomness = epoch_randomness(parent_id)?;
+
+        verifier.check_vrf(&pre_digest, parent_timestamp, &randomness)?;
+    }
+
+    Ok(Some(pre_digest.authority_index))
+}
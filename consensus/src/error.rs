@@ -0,0 +1,176 @@
+use derive_more::{Display, From};
+use sp_consensus::Error as ConsensusError;
+use sp_runtime::ConsensusEngineId;
+
+use crate::{AuthorityIndex, SignatureScheme};
+
+/// Errors raised by the singleton block author, block import and finality gadget.
+#[derive(Debug, Display, From)]
+pub enum Error {
+    #[display(fmt = "{}", _0)]
+    Seal(SealError),
+    #[display(fmt = "Failed to fetch signature scheme from runtime: {}", _0)]
+    #[from(ignore)]
+    SignatureSchemeUnavailable(sp_blockchain::Error),
+    #[display(
+        fmt = "Runtime uses {:?} signatures for singleton consensus but node is using {:?}",
+        runtime,
+        node
+    )]
+    SignatureSchemeMismatch {
+        runtime: SignatureScheme,
+        node: SignatureScheme,
+    },
+    #[display(fmt = "Failed to fetch singleton authorities from runtime: {}", _0)]
+    #[from(ignore)]
+    AuthoritiesUnavailable(sp_blockchain::Error),
+    #[display(fmt = "Failed to fetch leader election mode from runtime: {}", _0)]
+    #[from(ignore)]
+    LeaderElectionUnavailable(sp_blockchain::Error),
+    #[display(fmt = "Failed to fetch block time from runtime: {}", _0)]
+    #[from(ignore)]
+    BlockTimeUnavailable(sp_blockchain::Error),
+    #[display(fmt = "Runtime block time is zero")]
+    ZeroBlockTime,
+    #[display(fmt = "Failed to fetch proof of work difficulty from runtime: {}", _0)]
+    #[from(ignore)]
+    PowDifficultyUnavailable(sp_blockchain::Error),
+    #[display(fmt = "Runtime doesn't allow proof of work seals")]
+    PowDisabled,
+    #[display(fmt = "Failed to fetch epoch randomness from runtime: {}", _0)]
+    #[from(ignore)]
+    RandomnessUnavailable(sp_blockchain::Error),
+    #[display(fmt = "VRF leader election is not supported by this node")]
+    VrfUnsupported,
+    #[display(fmt = "Runtime has no singleton block authority")]
+    NoBlockAuthority,
+    #[display(fmt = "Runtime has no singleton finality authority")]
+    NoFinalityAuthority,
+    #[display(fmt = "Key {} is not a singleton block or backup authority", _0)]
+    NotBlockAuthority(String),
+    #[display(fmt = "Key {} is not the singleton finality authority", _0)]
+    NotFinalityAuthority(String),
+    #[display(fmt = "Failed to select best chain: {}", _0)]
+    #[from(ignore)]
+    SelectChain(ConsensusError),
+    #[display(fmt = "Failed to create inherent data: {}", _0)]
+    #[from(ignore)]
+    InherentData(String),
+    #[display(fmt = "Failed to initialize proposer: {}", _0)]
+    #[from(ignore)]
+    ProposerInit(String),
+    #[display(fmt = "Failed proposing block: {}", _0)]
+    #[from(ignore)]
+    Proposal(String),
+    #[display(fmt = "Failed to import block: {}", _0)]
+    #[from(ignore)]
+    BlockImport(ConsensusError),
+    #[display(fmt = "Failed to decode gossip message: {}", _0)]
+    GossipDecode(codec::Error),
+    #[display(fmt = "Invalid finality proof")]
+    BadFinalityProof,
+    #[display(fmt = "Invalid justification for block {}", _0)]
+    #[from(ignore)]
+    BadJustification(String),
+    #[display(fmt = "Failed to access signing history: {}", _0)]
+    #[from(ignore)]
+    SigningHistory(sp_blockchain::Error),
+    #[display(fmt = "Signing history is corrupted")]
+    CorruptSigningHistory,
+    #[display(
+        fmt = "Refusing to seal block: key already sealed block #{} in slot {}",
+        number,
+        slot
+    )]
+    #[from(ignore)]
+    AlreadySealed { number: u64, slot: u64 },
+    #[display(
+        fmt = "Refusing to finalize block: key already finalized block #{}",
+        _0
+    )]
+    #[from(ignore)]
+    AlreadyFinalized(u64),
+    #[display(fmt = "Client error: {}", _0)]
+    Client(sp_blockchain::Error),
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Seal(err) => Some(err),
+            Error::SignatureSchemeUnavailable(err) => Some(err),
+            Error::AuthoritiesUnavailable(err) => Some(err),
+            Error::LeaderElectionUnavailable(err) => Some(err),
+            Error::BlockTimeUnavailable(err) => Some(err),
+            Error::PowDifficultyUnavailable(err) => Some(err),
+            Error::RandomnessUnavailable(err) => Some(err),
+            Error::SelectChain(err) => Some(err),
+            Error::BlockImport(err) => Some(err),
+            Error::GossipDecode(err) => Some(err),
+            Error::SigningHistory(err) => Some(err),
+            Error::Client(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for ConsensusError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::SelectChain(err) | Error::BlockImport(err) => err,
+            err => ConsensusError::Other(Box::new(err)),
+        }
+    }
+}
+
+impl From<Error> for String {
+    fn from(err: Error) -> Self {
+        err.to_string()
+    }
+}
+
+/// Reasons for rejecting the digest or seal of an imported header.
+#[derive(Debug, Display, Eq, PartialEq)]
+pub enum SealError {
+    #[display(fmt = "Unsealed header")]
+    Unsealed,
+    #[display(fmt = "Header seal for wrong engine: {:?}", _0)]
+    WrongEngine(ConsensusEngineId),
+    #[display(fmt = "Header with invalid seal")]
+    InvalidSeal,
+    #[display(fmt = "Header with more than one singleton seal")]
+    DuplicateSeal,
+    #[display(fmt = "Header with digest item from unexpected engine: {:?}", _0)]
+    UnexpectedEngine(ConsensusEngineId),
+    #[display(fmt = "Header without singleton pre-runtime digest")]
+    MissingPreDigest,
+    #[display(fmt = "Header with invalid singleton pre-runtime digest")]
+    InvalidPreDigest,
+    #[display(fmt = "Header with more than one singleton pre-runtime digest")]
+    DuplicatePreDigest,
+    #[display(fmt = "Header authored by unknown authority: {}", _0)]
+    UnknownAuthority(AuthorityIndex),
+    #[display(fmt = "Header timestamp too far in the future: {}", _0)]
+    FutureTimestamp(u64),
+    #[display(
+        fmt = "Header sealed by backup authority {} before its failover delay",
+        _0
+    )]
+    EarlyFailover(AuthorityIndex),
+    #[display(fmt = "Header without leader election VRF")]
+    MissingVrf,
+    #[display(fmt = "Header with leader election VRF without VRF leader election")]
+    UnexpectedVrf,
+    #[display(fmt = "Header for slot {} not later than its parent's slot", _0)]
+    StaleSlot(u64),
+    #[display(fmt = "Invalid leader election VRF")]
+    BadVrf,
+    #[display(fmt = "Authority {} was not elected for the header's slot", _0)]
+    NotSlotLeader(AuthorityIndex),
+    #[display(fmt = "Invalid seal signature")]
+    BadSignature,
+    #[display(fmt = "Header seal with insufficient proof of work")]
+    InsufficientWork,
+}
+
+impl std::error::Error for SealError {}
@@ -0,0 +1,149 @@
+//! Entry points used by the fuzz targets in `fuzz/`, exposing the code paths that handle input
+//! received from other peers. Only available with the `fuzzing` feature.
+
+use super::*;
+
+/// Verifies the seal of the given header against the given block authorities, followed by the
+/// VRF in its pre-runtime digest with VRF leader election.
+pub fn check_header<Block, P>(
+    authorities: Vec<P::Public>,
+    leader_election: LeaderElection,
+    randomness: &Randomness,
+    header: &mut Block::Header,
+    parent_timestamp: u64,
+    now: u64,
+) -> Result<(), SealError>
+where
+    Block: BlockT,
+    P: SingletonPair,
+    P::Signature: Codec,
+{
+    let verifier = header_verifier::<Block, P>(authorities, leader_election, None);
+    let (_, pre_digest) = verifier.check_header(header, parent_timestamp, now)?;
+
+    if leader_election == LeaderElection::Vrf {
+        verifier.check_vrf(&pre_digest, parent_timestamp, randomness)?;
+    }
+
+    Ok(())
+}
+
+/// Verifies the proof of work seal of the given header against the given difficulty.
+pub fn check_pow_header<Block, P>(
+    difficulty: u128,
+    header: &mut Block::Header,
+    parent_timestamp: u64,
+    now: u64,
+) -> Result<(), SealError>
+where
+    Block: BlockT,
+    P: SingletonPair,
+    P::Signature: Codec,
+{
+    header_verifier::<Block, P>(Vec::new(), LeaderElection::Fixed, Some(difficulty))
+        .check_pow_header(header, parent_timestamp, now)
+        .map(|_| ())
+}
+
+/// Decodes a singleton pre-runtime digest and checks its VRF against the given block
+/// authorities.
+pub fn check_vrf<Block, P>(
+    authorities: Vec<P::Public>,
+    randomness: &Randomness,
+    pre_digest: &[u8],
+    parent_timestamp: u64,
+) -> Result<(), SealError>
+where
+    Block: BlockT,
+    P: SingletonPair,
+    P::Signature: Codec,
+{
+    let pre_digest =
+        PreDigest::decode(&mut &pre_digest[..]).map_err(|_| SealError::InvalidPreDigest)?;
+
+    header_verifier::<Block, P>(authorities, LeaderElection::Vrf, None).check_vrf(
+        &pre_digest,
+        parent_timestamp,
+        randomness,
+    )
+}
+
+/// Evaluates the leader election VRF of the given key for the slot of the given timestamp, so
+/// that fuzz targets can get past the VRF proof check.
+pub fn vrf_sign<P: SingletonPair>(
+    pair: &P,
+    randomness: &Randomness,
+    timestamp: u64,
+) -> Option<VrfSignature> {
+    pair.vrf_sign(vrf_transcript(randomness, slot_at(timestamp, BLOCK_TIME)))
+        .map(|(signature, _)| signature)
+}
+
+fn header_verifier<Block, P>(
+    authorities: Vec<P::Public>,
+    leader_election: LeaderElection,
+    pow_difficulty: Option<u128>,
+) -> HeaderVerifier<Block, P>
+where
+    Block: BlockT,
+    P: SingletonPair,
+{
+    HeaderVerifier {
+        authorities: authorities.into_iter().map(Into::into).collect(),
+        leader_election,
+        block_time: BLOCK_TIME,
+        failover_delay: BLOCK_TIME * FAILOVER_BLOCKS,
+        pow_difficulty,
+        allowed_engines: Vec::new(),
+        _phantom: PhantomData,
+    }
+}
+
+/// Decodes and verifies a gossiped finality message against the given finality authority.
+pub fn check_finality_message<Hash, P>(authority: P::Public, data: &[u8]) -> Result<(), Error>
+where
+    Hash: AsRef<[u8]> + Decode,
+    P: SingletonPair,
+    P::Signature: Codec,
+{
+    super::check_finality_message::<Hash, P, _>(data, |_| Ok(authority.into())).map(|_| ())
+}
+
+/// Imports the given block through the singleton block import, which handles any justification
+/// attached to it.
+pub fn import_block<Block, Inner, Client, P>(
+    inner: Inner,
+    client: Arc<Client>,
+    finality_authority: P::Public,
+    block: BlockImportParams<Block, TransactionFor<Client, Block>>,
+) -> Result<ImportResult, ConsensusError>
+where
+    Block: BlockT,
+    P: SingletonPair,
+    P::Signature: Codec,
+    Client: HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + ProvideRuntimeApi<Block>,
+    Inner: BlockImport<Block, Transaction = TransactionFor<Client, Block>>,
+    Inner::Error: Into<ConsensusError>,
+{
+    // only the finality authority is used by the block import.
+    let config = SingletonConfig::<P> {
+        block_authority: finality_authority.clone().into(),
+        backup_authorities: Vec::new(),
+        block_time: BLOCK_TIME,
+        failover_delay: BLOCK_TIME * FAILOVER_BLOCKS,
+        leader_election: LeaderElection::Fixed,
+        pow_difficulty: None,
+        finality_authority: finality_authority.into(),
+        allowed_engines: Vec::new(),
+    };
+
+    let mut block_import = SingletonBlockImport::<_, _, _, P> {
+        inner,
+        client,
+        config_at: Some(Arc::new(move |_| Ok(config.clone()))),
+    };
+
+    block_import.import_block(block, HashMap::new())
+}
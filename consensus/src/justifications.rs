@@ -0,0 +1,139 @@
+//! Export and import of singleton finality justifications, so that operators can archive the
+//! finality of a chain and restore it on another node.
+
+use codec::{Decode, Encode};
+use log::{debug, warn};
+use sc_client_api::{Backend as BackendT, BlockBackend, Finalizer};
+use sp_api::BlockId;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, One};
+
+use crate::{can_finalize, ConfigAt, Error, SingletonFinalityJustification, SingletonPair};
+
+/// A finality justification along with the number and hash of the block it finalizes.
+pub type ExportedJustification<Block, Signature> = (
+    NumberFor<Block>,
+    <Block as BlockT>::Hash,
+    SingletonFinalityJustification<Signature>,
+);
+
+/// Outcome of importing exported justifications.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ImportedJustifications {
+    /// Number of blocks finalized with an imported justification.
+    pub finalized: usize,
+    /// Number of justifications of blocks which were already finalized, which were verified but
+    /// not imported.
+    pub skipped: usize,
+}
+
+/// Collects the singleton justifications stored for the canonical blocks from `from` to `to`,
+/// inclusive.
+///
+/// Blocks which were finalized implicitly by a descendant have no justification. Justifications
+/// that aren't singleton justifications, e.g. produced by GRANDPA, are skipped.
+pub fn export_justifications<Block, Client, P>(
+    client: &Client,
+    from: NumberFor<Block>,
+    to: NumberFor<Block>,
+) -> Result<Vec<ExportedJustification<Block, P::Signature>>, Error>
+where
+    Block: BlockT,
+    Client: BlockBackend<Block> + HeaderBackend<Block>,
+    P: SingletonPair,
+    P::Signature: Decode,
+{
+    let mut justifications = Vec::new();
+    let mut number = from;
+
+    while number <= to {
+        let hash = match client.hash(number).map_err(Error::Client)? {
+            Some(hash) => hash,
+            None => break,
+        };
+
+        if let Some(justification) = client
+            .justification(&BlockId::Hash(hash))
+            .map_err(Error::Client)?
+        {
+            match SingletonFinalityJustification::decode(&mut &justification[..]) {
+                Ok(justification) => justifications.push((number, hash, justification)),
+                Err(_) => warn!(
+                    target: "singleton",
+                    "Skipping justification of block #{} which isn't a singleton justification",
+                    number,
+                ),
+            }
+        }
+
+        number += One::one();
+    }
+
+    Ok(justifications)
+}
+
+/// Verifies the given justifications and finalizes the blocks they justify, in ascending order.
+///
+/// Each justification must be signed by the finality authority in effect at the parent of its
+/// block, as looked up with `config_at`, and the block must already be imported. Justifications of
+/// blocks which are already finalized are verified but skipped, as they can't be stored anymore.
+pub fn import_justifications<Block, Backend, Client, P>(
+    client: &Client,
+    config_at: &ConfigAt<Block, P>,
+    mut justifications: Vec<ExportedJustification<Block, P::Signature>>,
+) -> Result<ImportedJustifications, Error>
+where
+    Block: BlockT,
+    Backend: BackendT<Block>,
+    Client: Finalizer<Block, Backend>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+    P: SingletonPair,
+    P::Signature: Encode,
+{
+    justifications.sort_by_key(|(number, _, _)| *number);
+
+    let mut imported = ImportedJustifications::default();
+
+    for (number, hash, justification) in justifications {
+        let header = client
+            .header(BlockId::Hash(hash))
+            .map_err(Error::Client)?
+            .filter(|header| *header.number() == number)
+            .ok_or_else(|| {
+                Error::Client(sp_blockchain::Error::UnknownBlock(format!(
+                    "#{} ({:?})",
+                    number, hash
+                )))
+            })?;
+
+        // the finality authority in effect at the parent signs the block, as in the block import.
+        let config = config_at(&BlockId::Hash(*header.parent_hash()))?;
+
+        if !P::verify(
+            justification.as_ref(),
+            &header.hash(),
+            config.finality_authority.as_ref(),
+        ) {
+            return Err(Error::BadJustification(format!("#{} ({:?})", number, hash)));
+        }
+
+        if number <= client.info().finalized_number {
+            debug!(target: "singleton", "Block #{} is already finalized.", number);
+            imported.skipped += 1;
+            continue;
+        }
+
+        if !can_finalize(client, hash).map_err(Error::Client)? {
+            return Err(Error::Client(sp_blockchain::Error::NotInFinalizedChain));
+        }
+
+        client
+            .finalize_block(BlockId::Hash(hash), Some(justification.encode()), false)
+            .map_err(Error::Client)?;
+
+        imported.finalized += 1;
+    }
+
+    Ok(imported)
+}
@@ -2,95 +2,597 @@ use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use codec::{Decode, Encode};
+use codec::{Codec, Decode, Encode};
 use derive_more::{AsRef, From, Into};
 use futures::{future, FutureExt, StreamExt};
 use log::{debug, info, warn};
+use merlin::Transcript;
 use parking_lot::Mutex;
+use prometheus_endpoint::Registry;
 
-use sc_client_api::{Backend as BackendT, BlockchainEvents, Finalizer};
+use sc_client_api::{AuxStore, Backend as BackendT, BlockBackend, BlockchainEvents, Finalizer};
 use sc_network_gossip::{
-    GossipEngine, Network as GossipNetwork, ValidationResult as GossipValidationResult,
-    Validator as GossipValidator, ValidatorContext as GossipValidatorContext,
+    GossipEngine, Network as GossipNetwork, TopicNotification,
+    ValidationResult as GossipValidationResult, Validator as GossipValidator,
+    ValidatorContext as GossipValidatorContext,
 };
 use sp_api::{BlockId, ProvideRuntimeApi, TransactionFor};
-use sp_application_crypto::RuntimePublic;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
 use sp_consensus::{
-    import_queue::{BasicQueue, CacheKeyId, Verifier},
+    import_queue::{
+        BasicQueue, BoxFinalityProofImport, BoxJustificationImport, CacheKeyId, Verifier,
+    },
     BlockCheckParams, BlockImport, BlockImportParams, BlockOrigin, Environment as EnvironmentT,
     Error as ConsensusError, ForkChoiceStrategy, ImportResult, Proposal, Proposer, RecordProof,
     SelectChain as SelectChainT, SyncOracle as SyncOracleT,
 };
-use sp_core::{sr25519, Pair};
+use sp_core::{
+    blake2_256,
+    crypto::{IsWrappedBy, Public},
+    Pair, U256,
+};
+use sp_inherents::{InherentData, InherentDataProviders, InherentIdentifier, ProvideInherentData};
 use sp_runtime::{
-    generic::DigestItem,
-    traits::{Block as BlockT, Hash as HashT, Header as HeaderT},
+    generic::{Digest, DigestItem},
+    traits::{
+        Block as BlockT, Hash as HashT, Header as HeaderT, NumberFor, Saturating,
+        UniqueSaturatedInto,
+    },
     ConsensusEngineId, Justification,
 };
 
-pub const SINGLETON_ENGINE_ID: ConsensusEngineId = *b"SGTN";
+pub use audit::{
+    inspect_block, verify_chain, BlockInspection, ChainVerification, InspectedJustification,
+    InspectedSeal,
+};
+pub use consensus_primitives::{
+    AuthorityIndex, LeaderElection, PowPreDigest, PreDigest, Randomness, SignatureScheme,
+    SingletonApi, VrfSignature, DEFAULT_BLOCK_TIME, FINALITY_INHERENT_IDENTIFIER,
+    SINGLETON_ENGINE_ID, SINGLETON_POW_ENGINE_ID,
+};
+pub use error::{Error, SealError};
+pub use justifications::{
+    export_justifications, import_justifications, ExportedJustification, ImportedJustifications,
+};
+pub use protection::{
+    import_signing_history, load_signing_history, SigningHistory, SigningHistoryDb,
+};
+
+mod audit;
+mod error;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
+mod justifications;
+mod metrics;
+mod pending;
+mod protection;
+#[cfg(test)]
+mod sim;
+#[cfg(test)]
+mod tests;
+
+use metrics::Metrics;
+use pending::PendingJustifications;
+
 pub const SINGLETON_PROTOCOL_NAME: &[u8] = b"/barcamp/singleton/1";
 
+/// Maximum number of finality proofs kept around for blocks that haven't been imported yet.
+const MAX_PENDING_JUSTIFICATIONS: usize = 64;
+
+/// How long to keep a finality proof around waiting for the block it refers to be imported.
+const PENDING_JUSTIFICATION_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Index of the block authority in the runtime's list of singleton authorities. The authorities
+/// that follow it are backup authorities, in order.
+const BLOCK_AUTHORITY_INDEX: AuthorityIndex = 0;
+
+/// Block time of networks whose runtime doesn't set another one, see `SingletonConfig::block_time`.
+#[cfg(any(test, feature = "fuzzing"))]
+const BLOCK_TIME: Duration = Duration::from_millis(DEFAULT_BLOCK_TIME);
+
+/// Number of block times without a new block after which the first backup authority may seal a
+/// block. Each further backup authority waits as long again.
+const FAILOVER_BLOCKS: u32 = 3;
+
+/// How far ahead of the local clock a block timestamp may be, at most. It only covers clock
+/// differences between nodes, as a backup authority can seal a block this much before its
+/// failover delay has elapsed by timestamping it in the future.
+const MAX_TIMESTAMP_DRIFT: Duration = Duration::from_secs(1);
+
+/// Context used to derive the leader election value from a VRF output.
+const VRF_ELECTION_CONTEXT: &[u8] = b"singleton-leader-election";
+
+/// Number of nonces tried on a proposed block before proposing a new one.
+const POW_ROUNDS: u64 = 1_000_000;
+
+/// Time between attempts to mine a block.
+const POW_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A key pair type that can be used to sign singleton seals and finality justifications.
+pub trait SingletonPair: Pair {
+    /// The signature scheme the runtime must report for this key pair type to be usable.
+    const SIGNATURE_SCHEME: SignatureScheme;
+
+    /// Whether the key pair type can be used with VRF leader election.
+    const SUPPORTS_VRF: bool = false;
+
+    /// Evaluates the leader election VRF over the given transcript, returning its output and
+    /// proof along with the value compared against the election threshold.
+    fn vrf_sign(&self, _transcript: Transcript) -> Option<(VrfSignature, u128)> {
+        None
+    }
+
+    /// Verifies a leader election VRF output and proof of the given key, returning the value
+    /// compared against the election threshold.
+    fn vrf_verify(
+        _public: &Self::Public,
+        _transcript: Transcript,
+        _signature: &VrfSignature,
+    ) -> Option<u128> {
+        None
+    }
+}
+
+impl SingletonPair for consensus_primitives::sr25519::AuthorityPair {
+    const SIGNATURE_SCHEME: SignatureScheme = consensus_primitives::sr25519::SIGNATURE_SCHEME;
+
+    const SUPPORTS_VRF: bool = true;
+
+    fn vrf_sign(&self, transcript: Transcript) -> Option<(VrfSignature, u128)> {
+        let keypair: &schnorrkel::Keypair = sp_core::sr25519::Pair::from_ref(self).as_ref();
+        let (inout, proof, _) = keypair.vrf_sign(transcript);
+
+        let signature = VrfSignature {
+            output: consensus_primitives::VRFOutput(inout.to_output()),
+            proof: consensus_primitives::VRFProof(proof),
+        };
+
+        Some((signature, vrf_election_value(&inout)))
+    }
+
+    fn vrf_verify(
+        public: &Self::Public,
+        transcript: Transcript,
+        signature: &VrfSignature,
+    ) -> Option<u128> {
+        let public = schnorrkel::PublicKey::from_bytes(public.as_ref()).ok()?;
+        let (inout, _) = public
+            .vrf_verify(transcript, &signature.output.0, &signature.proof.0)
+            .ok()?;
+
+        Some(vrf_election_value(&inout))
+    }
+}
+
+impl SingletonPair for consensus_primitives::ed25519::AuthorityPair {
+    const SIGNATURE_SCHEME: SignatureScheme = consensus_primitives::ed25519::SIGNATURE_SCHEME;
+}
+
+impl SingletonPair for consensus_primitives::ecdsa::AuthorityPair {
+    const SIGNATURE_SCHEME: SignatureScheme = consensus_primitives::ecdsa::SIGNATURE_SCHEME;
+}
+
+/// Checks that the signature scheme recorded in the runtime at the best block matches the key
+/// pair type used by the node.
+pub fn check_signature_scheme<Block, Client, P>(client: &Client) -> Result<(), Error>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+    Client::Api: SingletonApi<Block, P::Public, Error = sp_blockchain::Error>,
+    P: SingletonPair,
+    P::Public: Codec,
+{
+    let best_hash = client.info().best_hash;
+    let scheme = client
+        .runtime_api()
+        .signature_scheme(&BlockId::Hash(best_hash))
+        .map_err(Error::SignatureSchemeUnavailable)?;
+
+    if scheme != P::SIGNATURE_SCHEME {
+        return Err(Error::SignatureSchemeMismatch {
+            runtime: scheme,
+            node: P::SIGNATURE_SCHEME,
+        });
+    }
+
+    Ok(())
+}
+
+/// Source of time for the block author and the verifier, so that they can be driven by a virtual
+/// clock in tests and simulations.
+pub trait Clock: Send + Sync {
+    /// Current time in milliseconds since the unix epoch.
+    fn now(&self) -> u64;
+
+    /// Blocks the calling thread for the given duration. Returns `false` if the clock was stopped
+    /// in the meantime, in which case the caller should stop.
+    fn sleep(&self, duration: Duration) -> bool;
+}
+
+/// The local system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        unix_time_millis()
+    }
+
+    fn sleep(&self, duration: Duration) -> bool {
+        thread::sleep(duration);
+        true
+    }
+}
+
+/// Provides the number and justification of the last finalized block as inherent data, so that the
+/// runtime can tell which blocks are finalized (e.g. to pay finality rewards). Only useful with the
+/// singleton finality gadget, as the runtime can't check other justifications.
+pub struct FinalityInherentDataProvider<Block, Client> {
+    client: Arc<Client>,
+    _phantom: PhantomData<Block>,
+}
+
+impl<Block, Client> FinalityInherentDataProvider<Block, Client> {
+    pub fn new(client: Arc<Client>) -> Self {
+        FinalityInherentDataProvider {
+            client,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Block, Client> ProvideInherentData for FinalityInherentDataProvider<Block, Client>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block> + BlockBackend<Block> + Send + Sync,
+{
+    fn inherent_identifier(&self) -> &'static InherentIdentifier {
+        &FINALITY_INHERENT_IDENTIFIER
+    }
+
+    fn provide_inherent_data(
+        &self,
+        inherent_data: &mut InherentData,
+    ) -> Result<(), sp_inherents::Error> {
+        let info = self.client.info();
+
+        // blocks finalized without a justification (e.g. genesis) aren't provided, the runtime
+        // learns about their finality once a later block is finalized.
+        match self
+            .client
+            .justification(&BlockId::Hash(info.finalized_hash))
+        {
+            Ok(Some(justification)) => inherent_data.put_data(
+                FINALITY_INHERENT_IDENTIFIER,
+                &(info.finalized_number, justification),
+            ),
+            Ok(None) => Ok(()),
+            Err(err) => Err(sp_inherents::Error::from(format!(
+                "Failed to get justification of finalized block: {:?}",
+                err
+            ))),
+        }
+    }
+
+    fn error_to_string(&self, _error: &[u8]) -> Option<String> {
+        None
+    }
+}
+
 #[derive(AsRef, Clone, From, Into)]
-pub struct SingletonBlockAuthority(sr25519::Public);
+pub struct SingletonBlockAuthority<Public>(Public);
 
 #[derive(AsRef, From, Into)]
-pub struct SingletonBlockAuthorityPair(sr25519::Pair);
+pub struct SingletonBlockAuthorityPair<P>(P);
 
 #[derive(AsRef, Clone, From, Into)]
-pub struct SingletonFinalityAuthority(sr25519::Public);
+pub struct SingletonFinalityAuthority<Public>(Public);
 
 #[derive(AsRef, From, Into)]
-pub struct SingletonFinalityAuthorityPair(sr25519::Pair);
+pub struct SingletonFinalityAuthorityPair<P>(P);
 
 #[derive(AsRef, Decode, Encode, From)]
-struct SingletonFinalityJustification(sr25519::Signature);
+pub struct SingletonFinalityJustification<Signature>(Signature);
 
 #[derive(AsRef, Decode, Encode, From)]
-struct SingletonSeal(sr25519::Signature);
+struct SingletonSeal<Signature>(Signature);
 
-impl<Block> From<SingletonSeal> for DigestItem<Block> {
-    fn from(seal: SingletonSeal) -> Self {
+impl<Hash, Signature: Encode> From<SingletonSeal<Signature>> for DigestItem<Hash> {
+    fn from(seal: SingletonSeal<Signature>) -> Self {
         DigestItem::Seal(SINGLETON_ENGINE_ID, seal.encode())
     }
 }
 
-struct SingletonVerifier<Block> {
-    authority: SingletonBlockAuthority,
+/// Seal of a block sealed with proof of work.
+#[derive(Decode, Encode)]
+struct PowSeal {
+    nonce: u64,
+}
+
+impl<Hash> From<PowSeal> for DigestItem<Hash> {
+    fn from(seal: PowSeal) -> Self {
+        DigestItem::Seal(SINGLETON_POW_ENGINE_ID, seal.encode())
+    }
+}
+
+struct HeaderVerifier<Block, P: Pair> {
+    /// The block authority followed by the backup authorities, indexed by
+    /// `PreDigest::authority_index`.
+    authorities: Vec<SingletonBlockAuthority<P::Public>>,
+    leader_election: LeaderElection,
+    block_time: Duration,
+    failover_delay: Duration,
+    /// Set if blocks may be sealed with proof of work.
+    pow_difficulty: Option<u128>,
+    allowed_engines: Vec<ConsensusEngineId>,
     _phantom: PhantomData<Block>,
 }
 
-impl<Block> SingletonVerifier<Block>
+impl<Block, P> HeaderVerifier<Block, P>
 where
     Block: BlockT,
+    P: SingletonPair,
+    P::Signature: Codec,
 {
-    fn check_header(&self, header: &mut Block::Header) -> Result<SingletonSeal, String> {
+    fn new(config: &SingletonConfig<P>) -> Self {
+        HeaderVerifier {
+            authorities: config.block_authorities(),
+            leader_election: config.leader_election,
+            block_time: config.block_time,
+            failover_delay: config.failover_delay,
+            pow_difficulty: config.pow_difficulty,
+            allowed_engines: config.allowed_engines.clone(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Checks that the header is sealed by the authority named in its pre-runtime digest,
+    /// removing the seal from it.
+    ///
+    /// The seal must be the last digest item and the only singleton seal in the digest. Digest
+    /// items of other engines are rejected unless the engine is in `allowed_engines`. No block may
+    /// be timestamped too far ahead of `now`, and the runtime checks that the timestamp inherent
+    /// matches the pre-runtime digest's timestamp. With fixed leader election, backup authorities
+    /// may only seal a block once their failover delay has elapsed since the parent's timestamp.
+    /// With VRF leader election the VRF must be checked separately with `check_vrf`.
+    fn check_header(
+        &self,
+        header: &mut Block::Header,
+        parent_timestamp: u64,
+        now: u64,
+    ) -> Result<(SingletonSeal<P::Signature>, PreDigest), SealError> {
         let seal = match header.digest_mut().pop() {
             Some(DigestItem::Seal(id, seal)) => {
                 if id == SINGLETON_ENGINE_ID {
-                    SingletonSeal::decode(&mut &seal[..])
-                        .map_err(|_| "Header with invalid seal".to_string())?
+                    SingletonSeal::decode(&mut &seal[..]).map_err(|_| SealError::InvalidSeal)?
                 } else {
-                    return Err("Header seal for wrong engine".into());
+                    return Err(SealError::WrongEngine(id));
                 }
             }
-            _ => return Err("Unsealed header".into()),
+            _ => return Err(SealError::Unsealed),
         };
 
+        let pre_digest: PreDigest = self.find_pre_digest(header, SINGLETON_ENGINE_ID)?;
+
+        let authority = self
+            .authorities
+            .get(pre_digest.authority_index as usize)
+            .ok_or(SealError::UnknownAuthority(pre_digest.authority_index))?;
+
+        if pre_digest.timestamp >= now.saturating_add(self.max_drift_millis()) {
+            return Err(SealError::FutureTimestamp(pre_digest.timestamp));
+        }
+
+        if self.leader_election == LeaderElection::Fixed {
+            if pre_digest.vrf.is_some() {
+                return Err(SealError::UnexpectedVrf);
+            }
+
+            let elapsed = pre_digest.timestamp.saturating_sub(parent_timestamp);
+            if elapsed < failover_delay_millis(self.failover_delay, pre_digest.authority_index) {
+                return Err(SealError::EarlyFailover(pre_digest.authority_index));
+            }
+        }
+
         let pre_hash = header.hash();
-        if !self.authority.as_ref().verify(&pre_hash, seal.as_ref()) {
-            return Err("Invalid seal signature.".into());
+        if !P::verify(seal.as_ref(), &pre_hash, authority.as_ref()) {
+            return Err(SealError::BadSignature);
         }
 
-        Ok(seal)
+        Ok((seal, pre_digest))
+    }
+
+    /// Checks that the header is sealed with enough proof of work, removing the seal from it.
+    ///
+    /// The digest must follow the same rules as with authority seals, using the proof of work
+    /// engine id instead of the singleton one. The header's slot must be later than the parent's,
+    /// so that at most one block per block time is mined on a chain.
+    fn check_pow_header(
+        &self,
+        header: &mut Block::Header,
+        parent_timestamp: u64,
+        now: u64,
+    ) -> Result<(PowSeal, PowPreDigest), SealError> {
+        let difficulty = self
+            .pow_difficulty
+            .ok_or(SealError::WrongEngine(SINGLETON_POW_ENGINE_ID))?;
+
+        let seal = match header.digest_mut().pop() {
+            Some(DigestItem::Seal(id, seal)) => {
+                if id == SINGLETON_POW_ENGINE_ID {
+                    PowSeal::decode(&mut &seal[..]).map_err(|_| SealError::InvalidSeal)?
+                } else {
+                    return Err(SealError::WrongEngine(id));
+                }
+            }
+            _ => return Err(SealError::Unsealed),
+        };
+
+        let pre_digest: PowPreDigest = self.find_pre_digest(header, SINGLETON_POW_ENGINE_ID)?;
+
+        if pre_digest.timestamp >= now.saturating_add(self.max_drift_millis()) {
+            return Err(SealError::FutureTimestamp(pre_digest.timestamp));
+        }
+
+        let slot = slot_at(pre_digest.timestamp, self.block_time);
+        if slot <= slot_at(parent_timestamp, self.block_time) {
+            return Err(SealError::StaleSlot(slot));
+        }
+
+        if !meets_difficulty(&pow_hash(header.hash().as_ref(), seal.nonce), difficulty) {
+            return Err(SealError::InsufficientWork);
+        }
+
+        Ok((seal, pre_digest))
+    }
+
+    /// Returns how far ahead of the local clock a block may be timestamped, exclusive.
+    ///
+    /// With VRF leader election, the slot is derived from the timestamp, so the drift must also be
+    /// smaller than one block time for authorities not to claim slots they might be elected for
+    /// ahead of time.
+    fn max_drift_millis(&self) -> u64 {
+        let max_drift = match self.leader_election {
+            LeaderElection::Fixed => MAX_TIMESTAMP_DRIFT,
+            LeaderElection::Vrf => MAX_TIMESTAMP_DRIFT.min(self.block_time),
+        };
+
+        max_drift.as_millis() as u64
+    }
+
+    /// Decodes the pre-runtime digest of the given engine from the header, whose seal must have
+    /// been removed already.
+    ///
+    /// The header must have exactly one pre-runtime digest and no seal of that engine, and no
+    /// digest items of other engines unless they are in `allowed_engines`.
+    fn find_pre_digest<D: Decode>(
+        &self,
+        header: &Block::Header,
+        engine: ConsensusEngineId,
+    ) -> Result<D, SealError> {
+        let mut pre_digest = None;
+
+        for item in header.digest().logs() {
+            let engine_id = match item {
+                DigestItem::Seal(id, _) if *id == engine => return Err(SealError::DuplicateSeal),
+                DigestItem::PreRuntime(id, data) if *id == engine => {
+                    if pre_digest.is_some() {
+                        return Err(SealError::DuplicatePreDigest);
+                    }
+
+                    let decoded =
+                        D::decode(&mut &data[..]).map_err(|_| SealError::InvalidPreDigest)?;
+                    pre_digest = Some(decoded);
+                    continue;
+                }
+                DigestItem::Seal(id, _)
+                | DigestItem::PreRuntime(id, _)
+                | DigestItem::Consensus(id, _) => id,
+                _ => continue,
+            };
+
+            if !self.allowed_engines.contains(engine_id) {
+                return Err(SealError::UnexpectedEngine(*engine_id));
+            }
+        }
+
+        pre_digest.ok_or(SealError::MissingPreDigest)
+    }
+
+    /// Checks that the author of a block was elected for its slot, which must be later than the
+    /// parent's slot, by the VRF in its pre-runtime digest.
+    fn check_vrf(
+        &self,
+        pre_digest: &PreDigest,
+        parent_timestamp: u64,
+        randomness: &Randomness,
+    ) -> Result<(), SealError> {
+        let signature = pre_digest.vrf.as_ref().ok_or(SealError::MissingVrf)?;
+
+        let slot = slot_at(pre_digest.timestamp, self.block_time);
+        if slot <= slot_at(parent_timestamp, self.block_time) {
+            return Err(SealError::StaleSlot(slot));
+        }
+
+        let authority = self
+            .authorities
+            .get(pre_digest.authority_index as usize)
+            .ok_or(SealError::UnknownAuthority(pre_digest.authority_index))?;
+
+        let value = P::vrf_verify(
+            authority.as_ref(),
+            vrf_transcript(randomness, slot),
+            signature,
+        )
+        .ok_or(SealError::BadVrf)?;
+
+        if value >= vrf_threshold(self.authorities.len()) {
+            return Err(SealError::NotSlotLeader(pre_digest.authority_index));
+        }
+
+        Ok(())
     }
 }
 
-impl<Block> Verifier<Block> for SingletonVerifier<Block>
+/// Fetches the epoch randomness in effect for the children of the given block.
+pub type EpochRandomness<Block> =
+    Box<dyn Fn(&BlockId<Block>) -> Result<Randomness, Error> + Send + Sync>;
+
+/// Reads the epoch randomness from the runtime, for VRF leader election.
+pub fn runtime_epoch_randomness<Block, Client, P>(client: Arc<Client>) -> EpochRandomness<Block>
 where
     Block: BlockT,
+    Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+    Client::Api: SingletonApi<Block, P::Public, Error = sp_blockchain::Error>,
+    P: SingletonPair,
+    P::Public: Codec,
+{
+    Box::new(move |at| {
+        client
+            .runtime_api()
+            .epoch_randomness(at)
+            .map_err(Error::RandomnessUnavailable)
+    })
+}
+
+/// Fetches the singleton configuration in effect for the children of the given block, i.e. the
+/// authorities allowed to seal and finalize them. The authorities change whenever the session
+/// validators rotate their keys, so the configuration is looked up for every block.
+pub type ConfigAt<Block, P> =
+    Arc<dyn Fn(&BlockId<Block>) -> Result<SingletonConfig<P>, Error> + Send + Sync>;
+
+/// Reads the singleton configuration from the runtime, see `SingletonConfig::from_runtime`.
+pub fn runtime_config_at<Block, Client, P>(
+    client: Arc<Client>,
+    allowed_engines: Vec<ConsensusEngineId>,
+) -> ConfigAt<Block, P>
+where
+    Block: BlockT,
+    Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+    Client::Api: SingletonApi<Block, P::Public, Error = sp_blockchain::Error>,
+    P: SingletonPair,
+    P::Public: Codec,
+{
+    Arc::new(move |at| SingletonConfig::from_runtime(&*client, at, allowed_engines.clone()))
+}
+
+struct SingletonVerifier<Block, Client, P: Pair> {
+    config_at: ConfigAt<Block, P>,
+    client: Arc<Client>,
+    /// Only needed with VRF leader election.
+    epoch_randomness: Option<EpochRandomness<Block>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<Block, Client, P> Verifier<Block> for SingletonVerifier<Block, Client, P>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block> + Send + Sync,
+    P: SingletonPair,
+    P::Signature: Codec,
 {
     fn verify(
         &mut self,
@@ -106,33 +608,142 @@ where
         String,
     > {
         let hash = header.hash();
-        let seal = self.check_header(&mut header)?;
-
-        let mut import_params = BlockImportParams::new(origin, header);
+        let parent_hash = *header.parent_hash();
+        let parent_timestamp = self
+            .client
+            .header(BlockId::Hash(parent_hash))
+            .map_err(Error::Client)?
+            .map(|parent| header_timestamp(&parent))
+            .ok_or_else(|| {
+                Error::Client(sp_blockchain::Error::UnknownBlock(format!(
+                    "{:?}",
+                    parent_hash
+                )))
+            })?;
+
+        let config = (self.config_at)(&BlockId::Hash(parent_hash))?;
+        let header_verifier = HeaderVerifier::<Block, P>::new(&config);
+
+        let sealed_with_work = match header.digest().logs().last() {
+            Some(DigestItem::Seal(id, _)) => *id == SINGLETON_POW_ENGINE_ID,
+            _ => false,
+        };
 
-        import_params.body = body;
-        import_params.post_digests.push(seal.into());
-        import_params.post_hash = Some(hash);
+        if sealed_with_work {
+            let (seal, pre_digest) = header_verifier
+                .check_pow_header(&mut header, parent_timestamp, self.clock.now())
+                .map_err(Error::from)?;
+
+            debug!(
+                target: "singleton",
+                "Block {:?} sealed with proof of work, {}ms after its parent.",
+                hash,
+                pre_digest.timestamp.saturating_sub(parent_timestamp),
+            );
+
+            return Ok((
+                import_params(origin, header, hash, seal.into(), body, justification),
+                None,
+            ));
+        }
 
-        import_params.justification = justification;
-        import_params.finalized = false;
+        let (seal, pre_digest) = header_verifier
+            .check_header(&mut header, parent_timestamp, self.clock.now())
+            .map_err(Error::from)?;
 
-        import_params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+        if config.leader_election == LeaderElection::Vrf {
+            let epoch_randomness = self
+                .epoch_randomness
+                .as_ref()
+                .ok_or(Error::VrfUnsupported)?;
+            let randomness = epoch_randomness(&BlockId::Hash(parent_hash))?;
+
+            header_verifier
+                .check_vrf(&pre_digest, parent_timestamp, &randomness)
+                .map_err(Error::from)?;
+        } else if pre_digest.authority_index != BLOCK_AUTHORITY_INDEX {
+            info!(
+                target: "singleton",
+                "Block {:?} sealed by backup authority #{}, {}ms after its parent.",
+                hash,
+                pre_digest.authority_index,
+                pre_digest.timestamp.saturating_sub(parent_timestamp),
+            );
+        }
 
-        Ok((import_params, None))
+        Ok((
+            import_params(origin, header, hash, seal.into(), body, justification),
+            None,
+        ))
     }
 }
 
-struct SingletonBlockImport<Inner, Client> {
+/// Builds the import params of a verified block, whose seal has been removed from its header.
+fn import_params<Block: BlockT>(
+    origin: BlockOrigin,
+    header: Block::Header,
+    hash: Block::Hash,
+    seal: DigestItem<Block::Hash>,
+    body: Option<Vec<Block::Extrinsic>>,
+    justification: Option<Justification>,
+) -> BlockImportParams<Block, ()> {
+    let mut import_params = BlockImportParams::new(origin, header);
+
+    import_params.body = body;
+    import_params.post_digests.push(seal);
+    import_params.post_hash = Some(hash);
+
+    import_params.justification = justification;
+    import_params.finalized = false;
+
+    import_params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+
+    import_params
+}
+
+/// Block import finalizing blocks imported with a justification from the singleton finality
+/// authority. Used by the singleton import queue, and by the import queues of other block
+/// production engines when singleton finality is in use.
+///
+/// Justifications are checked against the finality authority in effect at the parent of the
+/// justified block, like seals.
+pub struct SingletonBlockImport<Block, Inner, Client, P: Pair> {
     inner: Inner,
-    finality_authority: SingletonFinalityAuthority,
-    _phantom: PhantomData<Client>,
+    client: Arc<Client>,
+    /// Looks up the finality authority whose justifications finalize imported blocks, or `None`
+    /// if another finality gadget handles justifications, in which case they are passed to
+    /// `inner` untouched.
+    config_at: Option<ConfigAt<Block, P>>,
+}
+
+impl<Block, Inner, Client, P: Pair> SingletonBlockImport<Block, Inner, Client, P> {
+    pub fn new(inner: Inner, client: Arc<Client>, config_at: Option<ConfigAt<Block, P>>) -> Self {
+        SingletonBlockImport {
+            inner,
+            client,
+            config_at,
+        }
+    }
 }
 
-impl<Block, Inner, Client> BlockImport<Block> for SingletonBlockImport<Inner, Client>
+impl<Block, Inner: Clone, Client, P: Pair> Clone for SingletonBlockImport<Block, Inner, Client, P> {
+    fn clone(&self) -> Self {
+        SingletonBlockImport {
+            inner: self.inner.clone(),
+            client: self.client.clone(),
+            config_at: self.config_at.clone(),
+        }
+    }
+}
+
+impl<Block, Inner, Client, P> BlockImport<Block> for SingletonBlockImport<Block, Inner, Client, P>
 where
     Block: BlockT,
-    Client: ProvideRuntimeApi<Block>,
+    P: SingletonPair,
+    P::Signature: Codec,
+    Client: HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + ProvideRuntimeApi<Block>,
     Inner: BlockImport<Block, Transaction = TransactionFor<Client, Block>>,
     Inner::Error: Into<ConsensusError>,
 {
@@ -148,26 +759,64 @@ where
         mut block: BlockImportParams<Block, Self::Transaction>,
         new_cache: HashMap<CacheKeyId, Vec<u8>>,
     ) -> Result<ImportResult, Self::Error> {
+        let config_at = match &self.config_at {
+            Some(config_at) => config_at,
+            None => {
+                return self
+                    .inner
+                    .import_block(block, new_cache)
+                    .map_err(Into::into)
+            }
+        };
+
         let justification = block
             .justification
             .take()
-            .and_then(|j| SingletonFinalityJustification::decode(&mut &j[..]).ok());
+            .and_then(|j| SingletonFinalityJustification::<P::Signature>::decode(&mut &j[..]).ok());
 
         if let Some(justification) = justification {
-            let hash = block
-                .post_hash
-                .as_ref()
-                .expect("header has seal; must have post hash; qed.");
-
-            if self
-                .finality_authority
-                .as_ref()
-                .verify(hash, justification.as_ref())
-            {
-                block.justification = Some(justification.encode());
-                block.finalized = true;
-            } else {
+            // the verifier always sets the post hash, but blocks can also reach the import
+            // pipeline through other paths so we shouldn't rely on it.
+            let hash = block.post_hash();
+            let parent_hash = *block.header.parent_hash();
+
+            let finality_authority = match config_at(&BlockId::Hash(parent_hash)) {
+                Ok(config) => config.finality_authority,
+                Err(err) => {
+                    warn!(
+                        target: "singleton",
+                        "Failed to look up finality authority of block {:?}: {}",
+                        hash,
+                        err,
+                    );
+
+                    return self
+                        .inner
+                        .import_block(block, new_cache)
+                        .map_err(Into::into);
+                }
+            };
+            if !P::verify(justification.as_ref(), &hash, finality_authority.as_ref()) {
                 warn!(target: "singleton", "Invalid justification provided with block: {:?}", hash)
+            } else {
+                match is_descendent_of_finalized(&*self.client, parent_hash, *block.header.number())
+                {
+                    Ok(true) => {
+                        block.justification = Some(justification.encode());
+                        block.finalized = true;
+                    }
+                    Ok(false) => warn!(
+                        target: "singleton",
+                        "Ignoring justification for block {:?} not descending from finalized block",
+                        hash,
+                    ),
+                    Err(err) => warn!(
+                        target: "singleton",
+                        "Failed checking ancestry of justified block {:?}: {:?}",
+                        hash,
+                        err,
+                    ),
+                }
             }
         }
 
@@ -178,49 +827,220 @@ where
 }
 
 #[derive(Clone)]
-pub struct SingletonConfig {
-    pub block_authority: SingletonBlockAuthority,
-    pub finality_authority: SingletonFinalityAuthority,
+pub struct SingletonConfig<P: Pair> {
+    pub block_authority: SingletonBlockAuthority<P::Public>,
+    /// Authorities allowed to seal blocks when the block authority stops producing them, in
+    /// order.
+    pub backup_authorities: Vec<SingletonBlockAuthority<P::Public>>,
+    /// Time between blocks, which is also the length of the slots blocks are timestamped in.
+    pub block_time: Duration,
+    /// Time without a new block after which the first backup authority may seal one, the
+    /// second backup waits twice as long and so on.
+    pub failover_delay: Duration,
+    /// With VRF leader election the block authority and the backup authorities are all equally
+    /// likely to be elected for a slot, and `failover_delay` doesn't apply.
+    pub leader_election: LeaderElection,
+    /// Difficulty of proof of work seals, if blocks may also be sealed with proof of work.
+    pub pow_difficulty: Option<u128>,
+    pub finality_authority: SingletonFinalityAuthority<P::Public>,
+    /// Other consensus engines whose digest items may appear in block headers.
+    pub allowed_engines: Vec<ConsensusEngineId>,
+}
+
+impl<P: SingletonPair> SingletonConfig<P> {
+    /// Reads the leader election mode, the block time, the proof of work difficulty and the
+    /// block, backup and finality authorities from the runtime at the given block.
+    pub fn from_runtime<Block, Client>(
+        client: &Client,
+        at: &BlockId<Block>,
+        allowed_engines: Vec<ConsensusEngineId>,
+    ) -> Result<Self, Error>
+    where
+        Block: BlockT,
+        Client: ProvideRuntimeApi<Block>,
+        Client::Api: SingletonApi<Block, P::Public, Error = sp_blockchain::Error>,
+        P::Public: Codec,
+    {
+        let runtime_api = client.runtime_api();
+
+        let leader_election = runtime_api
+            .leader_election(at)
+            .map_err(Error::LeaderElectionUnavailable)?;
+
+        if leader_election == LeaderElection::Vrf && !P::SUPPORTS_VRF {
+            return Err(Error::VrfUnsupported);
+        }
+
+        let block_time = match runtime_api
+            .block_time(at)
+            .map_err(Error::BlockTimeUnavailable)?
+        {
+            0 => return Err(Error::ZeroBlockTime),
+            block_time => Duration::from_millis(block_time),
+        };
+
+        let pow_difficulty = runtime_api
+            .pow_difficulty(at)
+            .map_err(Error::PowDifficultyUnavailable)?;
+
+        let mut authorities = runtime_api
+            .authorities(at)
+            .map_err(Error::AuthoritiesUnavailable)?
+            .into_iter()
+            .skip(BLOCK_AUTHORITY_INDEX as usize);
+
+        let block_authority = authorities.next().ok_or(Error::NoBlockAuthority)?;
+
+        let finality_authority = runtime_api
+            .finality_authority(at)
+            .map_err(Error::AuthoritiesUnavailable)?
+            .ok_or(Error::NoFinalityAuthority)?;
+
+        Ok(SingletonConfig {
+            block_authority: block_authority.into(),
+            backup_authorities: authorities.map(Into::into).collect(),
+            block_time,
+            failover_delay: block_time * FAILOVER_BLOCKS,
+            leader_election,
+            pow_difficulty,
+            finality_authority: finality_authority.into(),
+            allowed_engines,
+        })
+    }
+
+    /// Returns the block authority followed by the backup authorities, indexed by
+    /// `PreDigest::authority_index`.
+    fn block_authorities(&self) -> Vec<SingletonBlockAuthority<P::Public>> {
+        std::iter::once(&self.block_authority)
+            .chain(&self.backup_authorities)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the authority index of the given key, which must be the block authority or one of
+    /// the backup authorities to be used to seal blocks.
+    pub fn block_authority_index(&self, public: &P::Public) -> Result<AuthorityIndex, Error>
+    where
+        P::Public: std::fmt::Display,
+    {
+        self.block_authorities()
+            .iter()
+            .position(|authority| authority.as_ref() == public)
+            .map(|index| index as AuthorityIndex)
+            .ok_or_else(|| Error::NotBlockAuthority(public.to_string()))
+    }
+
+    /// Checks that the given key is the finality authority, so that it can be used to sign
+    /// finality proofs.
+    pub fn ensure_finality_authority(&self, public: &P::Public) -> Result<(), Error>
+    where
+        P::Public: std::fmt::Display,
+    {
+        if self.finality_authority.as_ref() != public {
+            return Err(Error::NotFinalityAuthority(public.to_string()));
+        }
+
+        Ok(())
+    }
 }
 
 pub type SingletonImportQueue<Block, Client> = BasicQueue<Block, TransactionFor<Client, Block>>;
 
-pub fn import_queue<Block, Inner, Client>(
-    config: SingletonConfig,
+/// Creates the import queue verifying singleton seals against the configuration looked up with
+/// `config_at` at the parent of each block.
+///
+/// Justifications are verified against the singleton finality authority, unless a
+/// `justification_import` or a `finality_proof_import` is given, in which case another finality
+/// gadget (e.g. GRANDPA) is in use and justifications are left to it and to `inner`.
+pub fn import_queue<Block, Inner, Client, P>(
+    config_at: ConfigAt<Block, P>,
     inner: Inner,
-    _client: Arc<Client>,
+    justification_import: Option<BoxJustificationImport<Block>>,
+    finality_proof_import: Option<BoxFinalityProofImport<Block>>,
+    client: Arc<Client>,
     spawner: &impl sp_core::traits::SpawnNamed,
 ) -> SingletonImportQueue<Block, Client>
 where
     Block: BlockT,
-    Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+    Client: HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + ProvideRuntimeApi<Block>
+        + Send
+        + Sync
+        + 'static,
+    Client::Api: SingletonApi<Block, P::Public, Error = sp_blockchain::Error>,
     Inner: BlockImport<Block, Transaction = TransactionFor<Client, Block>> + Send + Sync + 'static,
     Inner::Error: Into<ConsensusError>,
+    P: SingletonPair,
+    P::Public: Codec,
+    P::Signature: Codec,
 {
-    let block_import = Box::new(SingletonBlockImport {
-        inner,
-        finality_authority: config.finality_authority,
-        _phantom: PhantomData::<Client>,
-    });
+    let verifier = SingletonVerifier::<Block, _, P> {
+        config_at: config_at.clone(),
+        client: client.clone(),
+        epoch_randomness: Some(runtime_epoch_randomness::<_, _, P>(client.clone())),
+        clock: Arc::new(SystemClock),
+    };
 
-    let verifier = SingletonVerifier {
-        authority: config.block_authority,
-        _phantom: PhantomData,
+    let finality_config_at = if justification_import.is_some() || finality_proof_import.is_some() {
+        None
+    } else {
+        Some(config_at)
     };
 
-    BasicQueue::new(verifier, block_import, None, None, spawner, None)
+    let block_import = Box::new(SingletonBlockImport::<_, _, _, P>::new(
+        inner,
+        client,
+        finality_config_at,
+    ));
+
+    BasicQueue::new(
+        verifier,
+        block_import,
+        justification_import,
+        finality_proof_import,
+        spawner,
+        None,
+    )
 }
 
-pub fn start_singleton_block_author<Block, Client, Inner, Environment, SelectChain, SyncOracle>(
-    authority_key: SingletonBlockAuthorityPair,
+/// Starts the singleton block author on a dedicated thread.
+///
+/// If `max_unfinalized_depth` is set, the author stops proposing new blocks whenever the
+/// distance between the best block and the last finalized block exceeds it, and resumes once
+/// finality catches up.
+///
+/// The authorities are looked up with `config_at` at the best block before proposing each block,
+/// and `authority_key` is only used while it's one of them. With fixed leader election, a backup
+/// authority (authority index greater than zero) only proposes a block once no block has been
+/// built on the best block for `authority_index * failover_delay`. With VRF leader election,
+/// every authority proposes a block in each slot it is elected for.
+///
+/// Blocks are proposed with the inherents created by `inherent_data_providers`, such as the
+/// timestamp required by runtimes that also support other block production engines.
+///
+/// Blocks are timestamped and proposed once per block time according to `clock`, which is the
+/// `SystemClock` outside of tests. The author thread exits once `clock` is stopped.
+///
+/// The block time is set at genesis, and looked up at the best block before starting.
+#[allow(clippy::too_many_arguments)]
+pub fn start_singleton_block_author<Block, Client, Inner, Environment, SelectChain, SyncOracle, P>(
+    config_at: ConfigAt<Block, P>,
+    authority_key: SingletonBlockAuthorityPair<P>,
+    max_unfinalized_depth: Option<u32>,
     mut inner: Inner,
-    _client: Arc<Client>,
+    client: Arc<Client>,
     mut environment: Environment,
     select_chain: SelectChain,
     mut sync_oracle: SyncOracle,
-) where
+    inherent_data_providers: InherentDataProviders,
+    prometheus_registry: Option<&Registry>,
+    clock: Arc<dyn Clock>,
+) -> Result<thread::JoinHandle<()>, Error>
+where
     Block: BlockT,
-    Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+    Client: AuxStore + HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+    Client::Api: SingletonApi<Block, P::Public, Error = sp_blockchain::Error>,
     Inner: BlockImport<Block, Transaction = TransactionFor<Client, Block>> + Send + Sync + 'static,
     Inner::Error: Into<ConsensusError>,
     Environment: EnvironmentT<Block> + Send + 'static,
@@ -228,36 +1048,138 @@ pub fn start_singleton_block_author<Block, Client, Inner, Environment, SelectCha
     Environment::Error: std::fmt::Debug,
     SelectChain: SelectChainT<Block> + 'static,
     SyncOracle: SyncOracleT + Send + 'static,
+    P: SingletonPair,
+    P::Public: Codec + std::fmt::Display,
+    P::Signature: Codec,
 {
-    const BLOCK_TIME_SECS: u64 = 10;
+    let block_time = config_at(&BlockId::Hash(client.info().best_hash))?.block_time;
+    let vrf_key = authority_key.as_ref().clone();
+    let public = vrf_key.public();
+    let runtime_client = client.clone();
+    let history_client = client.clone();
+    let author_clock = clock.clone();
+
+    let metrics = match prometheus_registry.map(Metrics::register) {
+        Some(Ok(metrics)) => Some(metrics),
+        Some(Err(err)) => {
+            warn!(target: "singleton", "Failed to register metrics: {:?}", err);
+            None
+        }
+        None => None,
+    };
+
+    let unfinalized_depth = move || -> u64 {
+        let info = client.info();
+        info.best_number
+            .saturating_sub(info.finalized_number)
+            .unique_saturated_into()
+    };
+
+    let failover_metrics = metrics.clone();
 
     let mut propose_block =
-        move || -> Result<Proposal<Block, TransactionFor<Client, Block>>, String> {
-            let best_header = select_chain
-                .best_chain()
-                .map_err(|err| format!("Failed to select best chain: {:?}", err))?;
+        move || -> Result<Option<Proposal<Block, TransactionFor<Client, Block>>>, Error> {
+            let best_header = select_chain.best_chain().map_err(Error::SelectChain)?;
+            let timestamp = author_clock.now();
+
+            let config = config_at(&BlockId::Hash(best_header.hash()))?;
+            let leader_election = config.leader_election;
+            let authorities = config.block_authorities();
+
+            // the key may only be allowed to seal blocks before or after a session change.
+            let authority_index = match config.block_authority_index(&public) {
+                Ok(authority_index) => authority_index,
+                Err(err) => {
+                    debug!(target: "singleton", "{} at block {:?}.", err, best_header.hash());
+                    return Ok(None);
+                }
+            };
+
+            if leader_election == LeaderElection::Fixed && authority_index != BLOCK_AUTHORITY_INDEX
+            {
+                let elapsed = timestamp.saturating_sub(header_timestamp(&best_header));
+
+                if elapsed < failover_delay_millis(config.failover_delay, authority_index) {
+                    debug!(
+                        target: "singleton",
+                        "Backup authority #{} waiting, last block authored {}ms ago.",
+                        authority_index,
+                        elapsed,
+                    );
+                    return Ok(None);
+                }
+
+                warn!(
+                    target: "singleton",
+                    "No block authored for {}ms, sealing block as backup authority #{}.",
+                    elapsed,
+                    authority_index,
+                );
+
+                if let Some(metrics) = failover_metrics.as_ref() {
+                    metrics.report_failover();
+                }
+            }
+
+            let vrf = if leader_election == LeaderElection::Vrf {
+                let slot = slot_at(timestamp, block_time);
+                if slot <= slot_at(header_timestamp(&best_header), block_time) {
+                    debug!(target: "singleton", "Slot {} already has a block.", slot);
+                    return Ok(None);
+                }
+
+                let randomness = runtime_client
+                    .runtime_api()
+                    .epoch_randomness(&BlockId::Hash(best_header.hash()))
+                    .map_err(Error::RandomnessUnavailable)?;
+
+                let (signature, value) = vrf_key
+                    .vrf_sign(vrf_transcript(&randomness, slot))
+                    .ok_or(Error::VrfUnsupported)?;
+
+                if value >= vrf_threshold(authorities.len()) {
+                    debug!(target: "singleton", "Not elected for slot {}.", slot);
+                    return Ok(None);
+                }
+
+                debug!(target: "singleton", "Elected for slot {}.", slot);
+                Some(signature)
+            } else {
+                None
+            };
 
             let proposer = futures::executor::block_on(environment.init(&best_header))
-                .map_err(|err| format!("Failed to initialize proposer: {:?}", err))?;
+                .map_err(|err| Error::ProposerInit(format!("{:?}", err)))?;
 
-            let inherent_data = Default::default();
-            let inherent_digest = Default::default();
+            let inherent_data = block_inherent_data(&inherent_data_providers, timestamp)?;
+            let inherent_digest = Digest {
+                logs: vec![pre_runtime_digest(authority_index, timestamp, vrf)],
+            };
             let proposal = futures::executor::block_on(proposer.propose(
                 inherent_data,
                 inherent_digest,
-                Duration::from_secs(BLOCK_TIME_SECS),
+                block_time,
                 RecordProof::No,
             ))
-            .map_err(|err| format!("Failed proposing block: {:?}", err))?;
+            .map_err(|err| Error::Proposal(format!("{:?}", err)))?;
 
-            Ok(proposal)
+            Ok(Some(proposal))
         };
 
-    let seal_block = move |header: &mut Block::Header| {
+    let seal_block = move |header: &mut Block::Header| -> Result<_, Error> {
+        // never seal a block at a height or in a slot this key already sealed one, e.g. before
+        // a restart.
+        protection::record_seal::<Block::Hash, _>(
+            &*history_client,
+            &authority_key.as_ref().public().to_raw_vec(),
+            (*header.number()).unique_saturated_into(),
+            slot_at(header_timestamp(header), block_time),
+        )?;
+
         let seal = {
             let hash = header.hash();
-            let seal = authority_key.as_ref().sign(hash.as_ref());
-            DigestItem::Seal(SINGLETON_ENGINE_ID, seal.encode())
+            let seal = SingletonSeal(authority_key.as_ref().sign(hash.as_ref()));
+            DigestItem::from(seal)
         };
 
         header.digest_mut().push(seal);
@@ -267,17 +1189,21 @@ pub fn start_singleton_block_author<Block, Client, Inner, Environment, SelectCha
             .pop()
             .expect("pushed seal above; length greater than zero; qed");
 
-        (post_hash, seal)
+        Ok((post_hash, seal))
     };
 
-    let mut author_block = move || -> Result<(), String> {
+    let mut author_block = move || -> Result<(), Error> {
         if sync_oracle.is_major_syncing() {
             debug!(target: "singleton", "Skipping proposal due to sync.");
+            return Ok(());
         }
 
-        let proposal = propose_block()?;
+        let proposal = match propose_block()? {
+            Some(proposal) => proposal,
+            None => return Ok(()),
+        };
         let (mut header, body) = proposal.block.deconstruct();
-        let (post_hash, seal) = seal_block(&mut header);
+        let (post_hash, seal) = seal_block(&mut header)?;
 
         let mut import_params = BlockImportParams::new(BlockOrigin::Own, header);
         import_params.post_digests.push(seal);
@@ -288,33 +1214,206 @@ pub fn start_singleton_block_author<Block, Client, Inner, Environment, SelectCha
 
         inner
             .import_block(import_params, HashMap::default())
-            .map_err(|err| format!("Failed to import authored block: {:?}", err))
+            .map_err(|err| Error::BlockImport(err.into()))
             .map(|_| ())
     };
 
-    thread::spawn(move || {
+    Ok(thread::spawn(move || {
+        let mut stalled = false;
+
         loop {
-            if let Err(err) = author_block() {
-                warn!(target: "singleton", "Failed to author block: {:?}", err);
+            let depth = unfinalized_depth();
+
+            if let Some(metrics) = metrics.as_ref() {
+                metrics.report_unfinalized_depth(depth);
+            }
+
+            let lagging = should_pause_authoring(depth, max_unfinalized_depth);
+
+            if lagging && !stalled {
+                warn!(
+                    target: "singleton",
+                    "Pausing block authoring: {} blocks since last finalized block.",
+                    depth,
+                );
+            } else if !lagging && stalled {
+                info!(
+                    target: "singleton",
+                    "Resuming block authoring: {} blocks since last finalized block.",
+                    depth,
+                );
+            }
+
+            stalled = lagging;
+
+            if let Some(metrics) = metrics.as_ref() {
+                metrics.report_authoring_stalled(stalled);
+            }
+
+            if !stalled {
+                if let Err(err) = author_block() {
+                    warn!(target: "singleton", "Failed to author block: {}", err);
+                }
+            }
+
+            if !clock.sleep(block_time) {
+                break;
+            }
+        }
+    }))
+}
+
+/// Starts mining blocks sealed with proof of work, if the runtime allows them.
+///
+/// No authority key is needed. A block is proposed on top of the best block once its slot has
+/// passed, and up to `POW_ROUNDS` nonces are tried on it before proposing again, so that the
+/// block includes recent transactions and inherents.
+///
+/// Like the block author, the miner follows `clock` and exits once it is stopped.
+pub fn start_singleton_pow_author<
+    Block,
+    Transaction,
+    Inner,
+    Environment,
+    SelectChain,
+    SyncOracle,
+    P,
+>(
+    config: &SingletonConfig<P>,
+    mut inner: Inner,
+    mut environment: Environment,
+    select_chain: SelectChain,
+    mut sync_oracle: SyncOracle,
+    inherent_data_providers: InherentDataProviders,
+    clock: Arc<dyn Clock>,
+) -> Result<thread::JoinHandle<()>, Error>
+where
+    Block: BlockT,
+    Transaction: Send + 'static,
+    Inner: BlockImport<Block, Transaction = Transaction> + Send + Sync + 'static,
+    Inner::Error: Into<ConsensusError>,
+    Environment: EnvironmentT<Block> + Send + 'static,
+    Environment::Proposer: Proposer<Block, Transaction = Transaction>,
+    Environment::Error: std::fmt::Debug,
+    SelectChain: SelectChainT<Block> + 'static,
+    SyncOracle: SyncOracleT + Send + 'static,
+    P: SingletonPair,
+{
+    let difficulty = config.pow_difficulty.ok_or(Error::PowDisabled)?;
+    let block_time = config.block_time;
+    let miner_clock = clock.clone();
+
+    let mut mine_block = move || -> Result<(), Error> {
+        if sync_oracle.is_major_syncing() {
+            debug!(target: "singleton", "Skipping proposal due to sync.");
+            return Ok(());
+        }
+
+        let best_header = select_chain.best_chain().map_err(Error::SelectChain)?;
+        let timestamp = miner_clock.now();
+
+        let slot = slot_at(timestamp, block_time);
+        if slot <= slot_at(header_timestamp(&best_header), block_time) {
+            debug!(target: "singleton", "Slot {} already has a block.", slot);
+            return Ok(());
+        }
+
+        let proposer = futures::executor::block_on(environment.init(&best_header))
+            .map_err(|err| Error::ProposerInit(format!("{:?}", err)))?;
+
+        let inherent_data = block_inherent_data(&inherent_data_providers, timestamp)?;
+        let inherent_digest = Digest {
+            logs: vec![pow_pre_runtime_digest(timestamp)],
+        };
+        let proposal = futures::executor::block_on(proposer.propose(
+            inherent_data,
+            inherent_digest,
+            block_time,
+            RecordProof::No,
+        ))
+        .map_err(|err| Error::Proposal(format!("{:?}", err)))?;
+
+        let (mut header, body) = proposal.block.deconstruct();
+        let pre_hash = header.hash();
+
+        let nonce = match (0..POW_ROUNDS)
+            .find(|nonce| meets_difficulty(&pow_hash(pre_hash.as_ref(), *nonce), difficulty))
+        {
+            Some(nonce) => nonce,
+            None => {
+                debug!(target: "singleton", "No nonce found for block on slot {}.", slot);
+                return Ok(());
             }
+        };
 
-            thread::sleep(Duration::from_secs(BLOCK_TIME_SECS));
+        header.digest_mut().push(PowSeal { nonce }.into());
+        let post_hash = header.hash();
+        let seal = header
+            .digest_mut()
+            .pop()
+            .expect("pushed seal above; length greater than zero; qed");
+
+        info!(
+            target: "singleton",
+            "Mined block {:?} with nonce {}.",
+            post_hash,
+            nonce,
+        );
+
+        let mut import_params = BlockImportParams::new(BlockOrigin::Own, header);
+        import_params.post_digests.push(seal);
+        import_params.body = Some(body);
+        import_params.storage_changes = Some(proposal.storage_changes);
+        import_params.post_hash = Some(post_hash);
+        import_params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+
+        inner
+            .import_block(import_params, HashMap::default())
+            .map_err(|err| Error::BlockImport(err.into()))
+            .map(|_| ())
+    };
+
+    Ok(thread::spawn(move || loop {
+        if let Err(err) = mine_block() {
+            warn!(target: "singleton", "Failed to mine block: {}", err);
+        }
+
+        if !clock.sleep(POW_RETRY_INTERVAL) {
+            break;
         }
-    });
+    }))
 }
 
-pub async fn start_singleton_finality_gadget<Block, Backend, Client, Network, SyncOracle>(
-    config: SingletonConfig,
-    authority_key: Option<SingletonFinalityAuthorityPair>,
+/// Starts the singleton finality gadget.
+///
+/// All nodes listen for finality messages gossiped by the finality authority. If
+/// `authority_key` is given this node also acts as the finality authority, signing the ancestor
+/// of the best block which is `confirmation_depth` blocks below it. Only the highest eligible
+/// ancestor is signed, which implicitly finalizes all of its unfinalized ancestors.
+///
+/// The finality authority of a block is the one in effect at its parent, looked up with
+/// `config_at`, so `authority_key` only signs blocks while it's the finality authority.
+pub async fn start_singleton_finality_gadget<Block, Backend, Client, Network, SyncOracle, P>(
+    config_at: ConfigAt<Block, P>,
+    authority_key: Option<SingletonFinalityAuthorityPair<P>>,
+    confirmation_depth: u32,
     client: Arc<Client>,
     network: Network,
     mut sync_oracle: SyncOracle,
 ) where
     Block: BlockT,
     Backend: BackendT<Block>,
-    Client: BlockchainEvents<Block> + Finalizer<Block, Backend> + Send + Sync,
+    Client: AuxStore
+        + BlockchainEvents<Block>
+        + Finalizer<Block, Backend>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + Send
+        + Sync,
     Network: GossipNetwork<Block> + Clone + Send + 'static,
     SyncOracle: SyncOracleT + Send + 'static,
+    P: SingletonPair,
+    P::Signature: Codec + Send,
 {
     let topic = <<Block::Header as HeaderT>::Hashing as HashT>::hash("singleton".as_bytes());
 
@@ -327,89 +1426,181 @@ pub async fn start_singleton_finality_gadget<Block, Backend, Client, Network, Sy
 
     let mut listener = {
         let client = client.clone();
-        gossip_engine
+        let config_at = config_at.clone();
+
+        // finality proofs for blocks that we haven't imported yet, these are applied as soon as
+        // the corresponding block is imported.
+        let mut pending_justifications =
+            PendingJustifications::new(MAX_PENDING_JUSTIFICATIONS, PENDING_JUSTIFICATION_TIMEOUT);
+
+        let messages = gossip_engine
             .lock()
             .messages_for(topic)
-            .for_each(move |notification| {
-                if sync_oracle.is_major_syncing() {
-                    debug!(target: "singleton", "Ignoring finality notification due to sync.");
-                }
+            .map(ListenerEvent::Message);
 
-                let message: SingletonFinalityMessage<Block::Hash> = match Decode::decode(
-                    &mut &notification.message[..],
-                ) {
-                    Ok(m) => m,
-                    Err(err) => {
-                        warn!(target: "singleton", "Failed to decode gossip message: {:?}", err);
-                        return future::ready(());
+        let imports = client
+            .import_notification_stream()
+            .map(|notification| ListenerEvent::Imported(notification.hash));
+
+        futures::stream::select(messages, imports).for_each(move |event| {
+            let notification = match event {
+                ListenerEvent::Message(notification) => notification,
+                ListenerEvent::Imported(hash) => {
+                    if let Some(proof) = pending_justifications.take(&hash, unix_time_millis()) {
+                        // the proof was only checked against the finality authority at our best
+                        // block when it was received.
+                        match finality_authority_of(&*client, &config_at, hash) {
+                            Ok(finality_authority)
+                                if P::verify(proof.as_ref(), &hash, finality_authority.as_ref()) =>
+                            {
+                                debug!(
+                                    target: "singleton",
+                                    "Applying pending finality proof: {:?}",
+                                    hash,
+                                );
+                                apply_finality_proof::<Block, Backend, _, _>(&*client, hash, proof);
+                            }
+                            Ok(_) => warn!(
+                                target: "singleton",
+                                "Dropping pending finality proof for block {:?} not signed by its finality authority",
+                                hash,
+                            ),
+                            Err(err) => warn!(target: "singleton", "{}", err),
+                        }
                     }
-                };
 
-                if let Some(peer) = notification.sender {
-                    info!("Got finality message from: {:?}", peer);
+                    return future::ready(());
                 }
+            };
 
-                if config
-                    .finality_authority
-                    .as_ref()
-                    .verify(&message.block_hash, message.proof.as_ref())
-                {
-                    if let Err(err) = client.finalize_block(
-                        BlockId::Hash(message.block_hash),
-                        Some(message.proof.encode()),
-                        true,
-                    ) {
-                        warn!(target: "singleton", "Failed finalizing block {:?}: {:?}",
-                            message.block_hash,
-                            err
-                        );
-                    }
-                } else {
-                    warn!(target: "singleton", "Failed verifying finality proof");
+            if sync_oracle.is_major_syncing() {
+                debug!(target: "singleton", "Ignoring finality notification due to sync.");
+            }
+
+            if let Some(peer) = notification.sender {
+                info!("Got finality message from: {:?}", peer);
+            }
+
+            let message = match check_finality_message::<Block::Hash, P, _>(
+                &notification.message,
+                |hash| finality_authority_of(&*client, &config_at, *hash),
+            ) {
+                Ok(message) => message,
+                Err(err) => {
+                    warn!(target: "singleton", "{}", err);
+                    return future::ready(());
                 }
+            };
+
+            match client.header(BlockId::Hash(message.block_hash)) {
+                Ok(Some(_)) => apply_finality_proof::<Block, Backend, _, _>(
+                    &*client,
+                    message.block_hash,
+                    message.proof,
+                ),
+                Ok(None) => {
+                    pending_justifications.insert(
+                        message.block_hash,
+                        message.proof,
+                        unix_time_millis(),
+                    );
+
+                    debug!(
+                        target: "singleton",
+                        "Queued finality proof for unknown block {:?} ({} pending)",
+                        message.block_hash,
+                        pending_justifications.len(),
+                    );
+                }
+                Err(err) => warn!(
+                    target: "singleton",
+                    "Failed fetching header for block {:?}: {:?}",
+                    message.block_hash,
+                    err,
+                ),
+            }
 
-                future::ready(())
-            })
+            future::ready(())
+        })
     };
 
-    let finality_authority = |authority_key: SingletonFinalityAuthorityPair| {
+    let finality_authority = |authority_key: SingletonFinalityAuthorityPair<P>| {
         let gossip_engine = gossip_engine.clone();
 
         client
             .import_notification_stream()
-            .for_each(move |notification| {
-                thread::sleep(Duration::from_secs(1));
-
-                if notification.is_new_best {
-                    let proof: SingletonFinalityJustification = authority_key
-                        .as_ref()
-                        .sign(notification.hash.as_ref())
-                        .into();
-
-                    let proof_encoded = proof.encode();
-
-                    // let proof_encoded = proof.encode();
-                    let message = SingletonFinalityMessage {
-                        block_hash: notification.hash,
-                        proof,
-                    };
-
-                    gossip_engine
-                        .lock()
-                        .gossip_message(topic, message.encode(), true);
-
-                    if let Err(err) = client.finalize_block(
-                        BlockId::Hash(notification.hash),
-                        Some(proof_encoded),
-                        true,
-                    ) {
-                        warn!(target: "singleton", "Failed finalizing block {:?}: {:?}",
-                            notification.hash,
-                            err
+            .filter(|notification| future::ready(notification.is_new_best))
+            .for_each(move |_| {
+                let target_hash = match finality_target::<Block, _>(&*client, confirmation_depth) {
+                    Ok(Some(target_hash)) => target_hash,
+                    Ok(None) => return future::ready(()),
+                    Err(err) => {
+                        warn!(target: "singleton", "Failed to compute finality target: {:?}", err);
+                        return future::ready(());
+                    }
+                };
+
+                match finality_authority_of(&*client, &config_at, target_hash) {
+                    Ok(finality_authority)
+                        if finality_authority.as_ref() == &authority_key.as_ref().public() => {}
+                    Ok(_) => {
+                        debug!(
+                            target: "singleton",
+                            "Not the finality authority of block {:?}.",
+                            target_hash,
                         );
+                        return future::ready(());
+                    }
+                    Err(err) => {
+                        warn!(target: "singleton", "{}", err);
+                        return future::ready(());
                     }
                 }
 
+                let target_number = match client.number(target_hash) {
+                    Ok(Some(number)) => number.unique_saturated_into(),
+                    Ok(None) => return future::ready(()),
+                    Err(err) => {
+                        warn!(target: "singleton", "Failed to fetch finality target: {:?}", err);
+                        return future::ready(());
+                    }
+                };
+
+                // never finalize a block conflicting with one this key already finalized.
+                if let Err(err) = protection::record_finality(
+                    &*client,
+                    &authority_key.as_ref().public().to_raw_vec(),
+                    target_number,
+                    target_hash,
+                ) {
+                    warn!(target: "singleton", "{}", err);
+                    return future::ready(());
+                }
+
+                let proof = SingletonFinalityJustification(
+                    authority_key.as_ref().sign(target_hash.as_ref()),
+                );
+
+                let proof_encoded = proof.encode();
+
+                let message = SingletonFinalityMessage {
+                    block_hash: target_hash,
+                    proof,
+                };
+
+                gossip_engine
+                    .lock()
+                    .gossip_message(topic, message.encode(), true);
+
+                if let Err(err) =
+                    client.finalize_block(BlockId::Hash(target_hash), Some(proof_encoded), true)
+                {
+                    warn!(target: "singleton", "Failed finalizing block {:?}: {:?}",
+                        target_hash,
+                        err
+                    );
+                }
+
                 future::ready(())
             })
     };
@@ -430,10 +1621,267 @@ pub async fn start_singleton_finality_gadget<Block, Backend, Client, Network, Sy
     }
 }
 
+/// Builds the pre-runtime digest identifying the block author to the runtime.
+fn pre_runtime_digest<Hash>(
+    authority_index: AuthorityIndex,
+    timestamp: u64,
+    vrf: Option<VrfSignature>,
+) -> DigestItem<Hash> {
+    let pre_digest = PreDigest {
+        authority_index,
+        timestamp,
+        vrf,
+    };
+
+    DigestItem::PreRuntime(SINGLETON_ENGINE_ID, pre_digest.encode())
+}
+
+/// Builds the pre-runtime digest of a block sealed with proof of work.
+fn pow_pre_runtime_digest<Hash>(timestamp: u64) -> DigestItem<Hash> {
+    DigestItem::PreRuntime(SINGLETON_POW_ENGINE_ID, PowPreDigest { timestamp }.encode())
+}
+
+/// Creates the inherent data of a block with the given pre-runtime digest timestamp, which the
+/// timestamp inherent must match.
+fn block_inherent_data(
+    inherent_data_providers: &InherentDataProviders,
+    timestamp: u64,
+) -> Result<InherentData, Error> {
+    let mut inherent_data = inherent_data_providers
+        .create_inherent_data()
+        .map_err(|err| Error::InherentData(format!("{:?}", err)))?;
+    inherent_data.replace_data(sp_timestamp::INHERENT_IDENTIFIER, &timestamp);
+
+    Ok(inherent_data)
+}
+
+/// Returns the timestamp of the given header's singleton or proof of work pre-runtime digest, or
+/// zero if it has none (i.e. the genesis block).
+fn header_timestamp<Header: HeaderT>(header: &Header) -> u64 {
+    let pre_runtime_digests = || {
+        header
+            .digest()
+            .logs()
+            .iter()
+            .filter_map(|item| item.as_pre_runtime())
+    };
+
+    PreDigest::find(pre_runtime_digests())
+        .map(|pre_digest| pre_digest.timestamp)
+        .or_else(|| {
+            PowPreDigest::find(pre_runtime_digests()).map(|pre_digest| pre_digest.timestamp)
+        })
+        .unwrap_or(0)
+}
+
+/// Time in milliseconds without a new block after which the authority with the given index may
+/// seal one. Zero for the block authority.
+fn failover_delay_millis(failover_delay: Duration, authority_index: AuthorityIndex) -> u64 {
+    (failover_delay.as_millis() as u64).saturating_mul(authority_index.into())
+}
+
+/// Returns the slot that the given timestamp falls in, slots being one block time long.
+fn slot_at(timestamp: u64, block_time: Duration) -> u64 {
+    timestamp / block_time.as_millis() as u64
+}
+
+/// Builds the transcript that the leader election VRF is evaluated over.
+fn vrf_transcript(randomness: &Randomness, slot: u64) -> Transcript {
+    let mut transcript = Transcript::new(&SINGLETON_ENGINE_ID);
+    transcript.append_message(b"epoch randomness", randomness);
+    transcript.append_u64(b"slot", slot);
+    transcript
+}
+
+/// Hash of a header sealed with proof of work, given its hash without the seal.
+fn pow_hash(pre_hash: &[u8], nonce: u64) -> U256 {
+    U256::from_big_endian(&blake2_256(&(pre_hash, nonce).encode()))
+}
+
+/// Whether a proof of work hash is at most `U256::MAX / difficulty`.
+fn meets_difficulty(hash: &U256, difficulty: u128) -> bool {
+    *hash <= U256::max_value() / U256::from(difficulty.max(1))
+}
+
+/// Derives the value compared against the election threshold from a VRF output.
+fn vrf_election_value(inout: &schnorrkel::vrf::VRFInOut) -> u128 {
+    u128::from_le_bytes(inout.make_bytes::<[u8; 16]>(VRF_ELECTION_CONTEXT))
+}
+
+/// Election threshold for a VRF output, such that each authority is elected for a slot with
+/// probability `1 / authorities` and one authority is elected per slot on average. Slots may
+/// still have no or several elected authorities, with forks resolved by the longest chain rule.
+fn vrf_threshold(authorities: usize) -> u128 {
+    u128::MAX / authorities.max(1) as u128
+}
+
+fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Whether the block author should stop proposing blocks given the current number of unfinalized
+/// blocks on top of the last finalized block.
+fn should_pause_authoring(unfinalized_depth: u64, max_unfinalized_depth: Option<u32>) -> bool {
+    max_unfinalized_depth.map_or(false, |max| unfinalized_depth > u64::from(max))
+}
+
+/// Returns the hash of the ancestor of the best block which is `confirmation_depth` blocks below
+/// it, or `None` if that block is already finalized.
+fn finality_target<Block, Client>(
+    client: &Client,
+    confirmation_depth: u32,
+) -> sp_blockchain::Result<Option<Block::Hash>>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block>,
+{
+    let info = client.info();
+    let target_number = info.best_number.saturating_sub(confirmation_depth.into());
+
+    if target_number <= info.finalized_number {
+        return Ok(None);
+    }
+
+    let mut header = client.expect_header(BlockId::Hash(info.best_hash))?;
+    while *header.number() > target_number {
+        header = client.expect_header(BlockId::Hash(*header.parent_hash()))?;
+    }
+
+    Ok(Some(header.hash()))
+}
+
+/// Checks whether a block with the given parent and number is a descendant of the last finalized
+/// block.
+fn is_descendent_of_finalized<Block, Client>(
+    client: &Client,
+    parent_hash: Block::Hash,
+    number: NumberFor<Block>,
+) -> sp_blockchain::Result<bool>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block> + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+{
+    let info = client.info();
+
+    if number <= info.finalized_number {
+        return Ok(false);
+    }
+
+    if parent_hash == info.finalized_hash {
+        return Ok(true);
+    }
+
+    let route = sp_blockchain::tree_route(client, info.finalized_hash, parent_hash)?;
+
+    Ok(route.retracted().is_empty())
+}
+
+/// Checks whether the given block is known and descends from the last finalized block.
+fn can_finalize<Block, Client>(client: &Client, hash: Block::Hash) -> sp_blockchain::Result<bool>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block> + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+{
+    match client.header(BlockId::Hash(hash))? {
+        Some(header) => is_descendent_of_finalized(client, *header.parent_hash(), *header.number()),
+        None => Ok(false),
+    }
+}
+
+/// Finalizes the given (already imported) block with a verified finality proof, as long as it
+/// descends from the last finalized block.
+fn apply_finality_proof<Block, Backend, Client, Signature>(
+    client: &Client,
+    hash: Block::Hash,
+    proof: SingletonFinalityJustification<Signature>,
+) where
+    Block: BlockT,
+    Signature: Encode,
+    Backend: BackendT<Block>,
+    Client: Finalizer<Block, Backend>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+{
+    match can_finalize(client, hash) {
+        Ok(true) => {}
+        Ok(false) => {
+            debug!(
+                target: "singleton",
+                "Ignoring finality proof for block {:?} not descending from last finalized block",
+                hash,
+            );
+            return;
+        }
+        Err(err) => {
+            warn!(target: "singleton", "Failed checking ancestry of block {:?}: {:?}", hash, err);
+            return;
+        }
+    }
+
+    if let Err(err) = client.finalize_block(BlockId::Hash(hash), Some(proof.encode()), true) {
+        warn!(target: "singleton", "Failed finalizing block {:?}: {:?}", hash, err);
+    }
+}
+
+/// Returns the finality authority whose justifications finalize the given block, i.e. the one in
+/// effect at its parent. For blocks that aren't imported yet, returns the one in effect at the
+/// best block, which is most likely the same.
+fn finality_authority_of<Block, Client, P>(
+    client: &Client,
+    config_at: &ConfigAt<Block, P>,
+    hash: Block::Hash,
+) -> Result<SingletonFinalityAuthority<P::Public>, Error>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block>,
+    P: Pair,
+{
+    let at = match client.header(BlockId::Hash(hash)).map_err(Error::Client)? {
+        Some(header) => *header.parent_hash(),
+        None => client.info().best_hash,
+    };
+
+    config_at(&BlockId::Hash(at)).map(|config| config.finality_authority)
+}
+
+/// Decodes a gossiped finality message and checks that its proof was signed by the finality
+/// authority of the justified block, as returned by `finality_authority`.
+fn check_finality_message<Hash, P, F>(
+    data: &[u8],
+    finality_authority: F,
+) -> Result<SingletonFinalityMessage<Hash, P::Signature>, Error>
+where
+    Hash: AsRef<[u8]> + Decode,
+    P: SingletonPair,
+    P::Signature: Codec,
+    F: FnOnce(&Hash) -> Result<SingletonFinalityAuthority<P::Public>, Error>,
+{
+    let message = SingletonFinalityMessage::<Hash, P::Signature>::decode(&mut &data[..])?;
+    let finality_authority = finality_authority(&message.block_hash)?;
+
+    if !P::verify(
+        message.proof.as_ref(),
+        &message.block_hash,
+        finality_authority.as_ref(),
+    ) {
+        return Err(Error::BadFinalityProof);
+    }
+
+    Ok(message)
+}
+
+enum ListenerEvent<Hash> {
+    Message(TopicNotification),
+    Imported(Hash),
+}
+
 #[derive(Decode, Encode)]
-struct SingletonFinalityMessage<Hash> {
+struct SingletonFinalityMessage<Hash, Signature> {
     block_hash: Hash,
-    proof: SingletonFinalityJustification,
+    proof: SingletonFinalityJustification<Signature>,
 }
 
 /// Allows all gossip messages to get through.
@@ -0,0 +1,49 @@
+use prometheus_endpoint::{register, Counter, Gauge, PrometheusError, Registry, U64};
+
+/// Prometheus metrics exposed by the singleton block author.
+#[derive(Clone)]
+pub(crate) struct Metrics {
+    unfinalized_depth: Gauge<U64>,
+    authoring_stalled: Gauge<U64>,
+    failovers: Counter<U64>,
+}
+
+impl Metrics {
+    pub(crate) fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            unfinalized_depth: register(
+                Gauge::new(
+                    "singleton_unfinalized_depth",
+                    "Number of blocks between the best and the last finalized block.",
+                )?,
+                registry,
+            )?,
+            authoring_stalled: register(
+                Gauge::new(
+                    "singleton_authoring_stalled",
+                    "Whether block authoring is paused waiting for finality (1) or not (0).",
+                )?,
+                registry,
+            )?,
+            failovers: register(
+                Counter::new(
+                    "singleton_failovers_total",
+                    "Number of blocks proposed by this node as a backup authority.",
+                )?,
+                registry,
+            )?,
+        })
+    }
+
+    pub(crate) fn report_unfinalized_depth(&self, depth: u64) {
+        self.unfinalized_depth.set(depth);
+    }
+
+    pub(crate) fn report_authoring_stalled(&self, stalled: bool) {
+        self.authoring_stalled.set(stalled as u64);
+    }
+
+    pub(crate) fn report_failover(&self) {
+        self.failovers.inc();
+    }
+}
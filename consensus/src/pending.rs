@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+
+/// A bounded buffer of finality proofs for blocks that haven't been imported yet.
+///
+/// Entries are dropped once they are older than the configured timeout, and the oldest entry is
+/// evicted whenever a new one is inserted while the buffer is full. Times are given in
+/// milliseconds, as returned by a `Clock`, so that a virtual clock can drive the buffer.
+pub(crate) struct PendingJustifications<BlockHash, Proof> {
+    entries: HashMap<BlockHash, (u64, Proof)>,
+    max_entries: usize,
+    timeout: Duration,
+}
+
+impl<BlockHash, Proof> PendingJustifications<BlockHash, Proof>
+where
+    BlockHash: Clone + Eq + Hash,
+{
+    pub(crate) fn new(max_entries: usize, timeout: Duration) -> Self {
+        PendingJustifications {
+            entries: HashMap::new(),
+            max_entries,
+            timeout,
+        }
+    }
+
+    /// Buffers the proof for the given block, replacing any existing proof for it.
+    pub(crate) fn insert(&mut self, hash: BlockHash, proof: Proof, now: u64) {
+        self.prune(now);
+
+        if self.max_entries == 0 {
+            return;
+        }
+
+        if !self.entries.contains_key(&hash) && self.entries.len() >= self.max_entries {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (inserted, _))| *inserted)
+                .map(|(hash, _)| hash.clone());
+
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(hash, (now, proof));
+    }
+
+    /// Takes the proof buffered for the given block, if any and if it hasn't expired yet.
+    pub(crate) fn take(&mut self, hash: &BlockHash, now: u64) -> Option<Proof> {
+        self.prune(now);
+        self.entries.remove(hash).map(|(_, proof)| proof)
+    }
+
+    /// Drops all entries that are older than the configured timeout.
+    pub(crate) fn prune(&mut self, now: u64) {
+        let timeout = self.timeout;
+        self.entries.retain(|_, (inserted, _)| {
+            Duration::from_millis(now.saturating_sub(*inserted)) < timeout
+        });
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_buffered_proof() {
+        let now = 1_000;
+        let mut pending = PendingJustifications::new(2, Duration::from_secs(10));
+
+        pending.insert(1u8, "a", now);
+
+        assert_eq!(pending.take(&2, now), None);
+        assert_eq!(pending.take(&1, now), Some("a"));
+        assert_eq!(pending.take(&1, now), None);
+    }
+
+    #[test]
+    fn evicts_oldest_entry_when_full() {
+        let now = 1_000;
+        let mut pending = PendingJustifications::new(2, Duration::from_secs(10));
+
+        pending.insert(1u8, "a", now);
+        pending.insert(2, "b", now + 1_000);
+        pending.insert(3, "c", now + 2_000);
+
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending.take(&1, now + 2_000), None);
+        assert_eq!(pending.take(&2, now + 2_000), Some("b"));
+        assert_eq!(pending.take(&3, now + 2_000), Some("c"));
+    }
+
+    #[test]
+    fn drops_expired_entries() {
+        let now = 1_000;
+        let mut pending = PendingJustifications::new(2, Duration::from_secs(10));
+
+        pending.insert(1u8, "a", now);
+        pending.insert(2, "b", now + 5_000);
+
+        assert_eq!(pending.take(&1, now + 10_000), None);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending.take(&2, now + 10_000), Some("b"));
+    }
+}
@@ -0,0 +1,253 @@
+//! Slashing protection for the local singleton keys.
+//!
+//! Before signing, the block author and the finality gadget check and record what the key has
+//! signed so far in the node's aux storage, so that a restarted node (or a second node using the
+//! same key and database) never seals two blocks at the same height or in the same slot, nor
+//! finalizes conflicting blocks.
+
+use std::collections::BTreeMap;
+
+use codec::{Decode, Encode};
+use parking_lot::{const_mutex, Mutex};
+use sc_client_api::AuxStore;
+
+use crate::Error;
+
+/// Aux storage key under which the signing history of all local keys is stored.
+const SIGNING_HISTORY_KEY: &[u8] = b"singleton_signing_history";
+
+/// Serializes updates of the signing history, which the block author and the finality gadget
+/// write from different threads.
+static SIGNING_HISTORY_LOCK: Mutex<()> = const_mutex(());
+
+/// What a single key has signed so far.
+#[derive(Clone, Debug, Decode, Encode, Eq, PartialEq)]
+pub struct SigningHistory<Hash> {
+    /// Number and slot of the highest block sealed with the key.
+    pub sealed: Option<(u64, u64)>,
+    /// Number and hash of the highest block finalized with the key.
+    pub finalized: Option<(u64, Hash)>,
+}
+
+impl<Hash> Default for SigningHistory<Hash> {
+    fn default() -> Self {
+        SigningHistory {
+            sealed: None,
+            finalized: None,
+        }
+    }
+}
+
+impl<Hash: PartialEq> SigningHistory<Hash> {
+    /// Merges the history of the same key recorded elsewhere into this one, keeping the highest
+    /// sealed number and slot and the highest finalized block.
+    fn merge(&mut self, other: SigningHistory<Hash>) {
+        self.sealed = match (self.sealed, other.sealed) {
+            (Some((number, slot)), Some((other_number, other_slot))) => {
+                Some((number.max(other_number), slot.max(other_slot)))
+            }
+            (sealed, other_sealed) => sealed.or(other_sealed),
+        };
+
+        let other_is_higher = match (&self.finalized, &other.finalized) {
+            (Some((number, _)), Some((other_number, _))) => other_number > number,
+            (None, Some(_)) => true,
+            _ => false,
+        };
+
+        if other_is_higher {
+            self.finalized = other.finalized;
+        }
+    }
+}
+
+/// Signing history of all local keys, indexed by their raw public key.
+pub type SigningHistoryDb<Hash> = BTreeMap<Vec<u8>, SigningHistory<Hash>>;
+
+/// Loads the signing history of all local keys from aux storage.
+pub fn load_signing_history<Hash, Aux>(aux: &Aux) -> Result<SigningHistoryDb<Hash>, Error>
+where
+    Hash: Decode,
+    Aux: AuxStore,
+{
+    match aux
+        .get_aux(SIGNING_HISTORY_KEY)
+        .map_err(Error::SigningHistory)?
+    {
+        Some(encoded) => {
+            SigningHistoryDb::decode(&mut &encoded[..]).map_err(|_| Error::CorruptSigningHistory)
+        }
+        None => Ok(SigningHistoryDb::new()),
+    }
+}
+
+/// Merges the given signing history, e.g. exported from another node, into the local one.
+pub fn import_signing_history<Hash, Aux>(
+    aux: &Aux,
+    imported: SigningHistoryDb<Hash>,
+) -> Result<(), Error>
+where
+    Hash: Decode + Encode + PartialEq,
+    Aux: AuxStore,
+{
+    update_signing_history(aux, |history| {
+        for (public, imported) in imported {
+            history.entry(public).or_default().merge(imported);
+        }
+
+        Ok(())
+    })
+}
+
+/// Checks that the key may seal a block with the given number and slot, and records it if so.
+///
+/// The key may only seal blocks higher than and in a later slot than any block it sealed before.
+pub fn record_seal<Hash, Aux>(aux: &Aux, public: &[u8], number: u64, slot: u64) -> Result<(), Error>
+where
+    Hash: Decode + Encode,
+    Aux: AuxStore,
+{
+    update_signing_history::<Hash, _, _>(aux, |history| {
+        let history = history.entry(public.to_vec()).or_default();
+
+        if let Some((sealed_number, sealed_slot)) = history.sealed {
+            if number <= sealed_number || slot <= sealed_slot {
+                return Err(Error::AlreadySealed {
+                    number: sealed_number,
+                    slot: sealed_slot,
+                });
+            }
+        }
+
+        history.sealed = Some((number, slot));
+        Ok(())
+    })
+}
+
+/// Checks that the key may finalize the given block, and records it if so.
+///
+/// The key may only finalize blocks higher than any block it finalized before, or sign the same
+/// block again.
+pub fn record_finality<Hash, Aux>(
+    aux: &Aux,
+    public: &[u8],
+    number: u64,
+    hash: Hash,
+) -> Result<(), Error>
+where
+    Hash: Decode + Encode + PartialEq,
+    Aux: AuxStore,
+{
+    update_signing_history(aux, |history| {
+        let history = history.entry(public.to_vec()).or_default();
+
+        if let Some((finalized_number, finalized_hash)) = &history.finalized {
+            if number < *finalized_number
+                || (number == *finalized_number && hash != *finalized_hash)
+            {
+                return Err(Error::AlreadyFinalized(*finalized_number));
+            }
+        }
+
+        history.finalized = Some((number, hash));
+        Ok(())
+    })
+}
+
+/// Applies the given change to the signing history, writing it back to aux storage if it
+/// succeeds.
+fn update_signing_history<Hash, Aux, F>(aux: &Aux, update: F) -> Result<(), Error>
+where
+    Hash: Decode + Encode,
+    Aux: AuxStore,
+    F: FnOnce(&mut SigningHistoryDb<Hash>) -> Result<(), Error>,
+{
+    let _lock = SIGNING_HISTORY_LOCK.lock();
+
+    let mut history = load_signing_history(aux)?;
+    update(&mut history)?;
+
+    aux.insert_aux(&[(SIGNING_HISTORY_KEY, &history.encode()[..])], &[])
+        .map_err(Error::SigningHistory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use substrate_test_runtime_client::runtime::Hash;
+
+    const KEY: &[u8] = b"key";
+
+    #[test]
+    fn refuses_to_seal_twice_at_the_same_height_or_slot() {
+        let client = substrate_test_runtime_client::new();
+
+        assert!(record_seal::<Hash, _>(&client, KEY, 1, 1).is_ok());
+
+        // a restarted author would try to seal at the same height again.
+        assert!(matches!(
+            record_seal::<Hash, _>(&client, KEY, 1, 2),
+            Err(Error::AlreadySealed { number: 1, slot: 1 })
+        ));
+        assert!(matches!(
+            record_seal::<Hash, _>(&client, KEY, 2, 1),
+            Err(Error::AlreadySealed { number: 1, slot: 1 })
+        ));
+
+        // other keys are tracked separately.
+        assert!(record_seal::<Hash, _>(&client, b"other", 1, 1).is_ok());
+        assert!(record_seal::<Hash, _>(&client, KEY, 2, 2).is_ok());
+    }
+
+    #[test]
+    fn refuses_to_finalize_conflicting_blocks() {
+        let client = substrate_test_runtime_client::new();
+        let (a, b) = (Hash::repeat_byte(1), Hash::repeat_byte(2));
+
+        assert!(record_finality(&client, KEY, 2, a).is_ok());
+        assert!(record_finality(&client, KEY, 2, a).is_ok());
+
+        assert!(matches!(
+            record_finality(&client, KEY, 2, b),
+            Err(Error::AlreadyFinalized(2))
+        ));
+        assert!(matches!(
+            record_finality(&client, KEY, 1, b),
+            Err(Error::AlreadyFinalized(2))
+        ));
+
+        assert!(record_finality(&client, KEY, 3, b).is_ok());
+    }
+
+    #[test]
+    fn imported_history_never_lowers_protection() {
+        let client = substrate_test_runtime_client::new();
+        let (a, b) = (Hash::repeat_byte(1), Hash::repeat_byte(2));
+
+        record_seal::<Hash, _>(&client, KEY, 5, 3).unwrap();
+        record_finality(&client, KEY, 4, a).unwrap();
+
+        let mut imported = SigningHistoryDb::new();
+        imported.insert(
+            KEY.to_vec(),
+            SigningHistory {
+                sealed: Some((2, 7)),
+                finalized: Some((3, b)),
+            },
+        );
+        imported.insert(b"other".to_vec(), SigningHistory::default());
+
+        import_signing_history(&client, imported).unwrap();
+
+        let history = load_signing_history::<Hash, _>(&client).unwrap();
+        assert_eq!(
+            history.get(KEY),
+            Some(&SigningHistory {
+                sealed: Some((5, 7)),
+                finalized: Some((4, a)),
+            })
+        );
+        assert_eq!(history.get(&b"other"[..]), Some(&SigningHistory::default()));
+    }
+}
@@ -0,0 +1,1367 @@
+//! Deterministic simulation of a singleton network.
+//!
+//! Virtual time, message delivery (delays, drops and partitions), node restarts and block
+//! authoring are all driven by a single seeded scheduler, so timing dependent scenarios can be
+//! replayed exactly. Simulated nodes keep their chain of headers in memory but run the same seal
+//! verification and finality logic as the finality gadget, and time out pending finality proofs
+//! on the virtual clock. Blocks are authored by the real block author, whose thread runs in
+//! lockstep with the scheduler through a `StepClock`.
+
+use super::*;
+
+use std::collections::{BTreeMap, HashSet};
+use std::panic;
+
+use consensus_primitives::ed25519::{AuthorityId, AuthorityPair, AuthoritySignature};
+use parking_lot::Condvar;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sc_client_api::ClientImportOperation;
+use sp_api::ApiRef;
+use sp_blockchain::{BlockStatus, CachedHeaderMetadata, Info};
+use sp_consensus::NoNetwork;
+use sp_inherents::InherentData;
+use sp_runtime::{traits::DigestFor, Justification};
+use substrate_test_runtime_client::{
+    runtime::{Block, Hash, Header},
+    Backend,
+};
+
+/// Virtual time in milliseconds since the start of the simulation.
+pub(crate) type Time = u64;
+
+/// Virtual clock driving a block author thread in lockstep with a test.
+///
+/// An author sleeping on the clock only wakes up once the test advances the clock past the time
+/// it sleeps until, and the test waits for it to finish authoring and go back to sleep, so that
+/// the author never runs concurrently with the test.
+pub(crate) struct StepClock {
+    state: Mutex<StepClockState>,
+    changed: Condvar,
+}
+
+struct StepClockState {
+    now: Time,
+    /// Time until which the author sleeps, if it is asleep.
+    wake_at: Option<Time>,
+    /// Set while the author is awake after being woken up by the clock.
+    awake: bool,
+    /// Set once the clock is stopped, after which the author never sleeps again.
+    stopped: bool,
+    /// Set once the author thread has exited, either because the clock was stopped or because
+    /// it panicked.
+    exited: bool,
+}
+
+impl StepClock {
+    pub(crate) fn new(now: Time) -> Self {
+        StepClock {
+            state: Mutex::new(StepClockState {
+                now,
+                wake_at: None,
+                awake: false,
+                stopped: false,
+                exited: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Waits for the author to go to sleep, returning the time it sleeps until, or `None` if the
+    /// author thread exited instead.
+    pub(crate) fn wait_asleep(&self) -> Option<Time> {
+        let mut state = self.state.lock();
+
+        loop {
+            if let Some(wake_at) = state.wake_at {
+                return Some(wake_at);
+            }
+
+            if state.exited {
+                return None;
+            }
+
+            self.changed.wait(&mut state);
+        }
+    }
+
+    /// Advances the clock to the given time, letting the author author a block if it wakes up.
+    pub(crate) fn advance_to(&self, time: Time) {
+        let mut state = self.state.lock();
+        state.now = time;
+
+        if state.wake_at.map_or(false, |wake_at| wake_at <= time) {
+            state.wake_at = None;
+            state.awake = true;
+            self.changed.notify_all();
+
+            while state.awake && !state.exited {
+                self.changed.wait(&mut state);
+            }
+        }
+    }
+
+    /// Stops the clock, which makes a sleeping author return and exit.
+    pub(crate) fn stop(&self) {
+        let mut state = self.state.lock();
+        state.stopped = true;
+        state.wake_at = None;
+        self.changed.notify_all();
+    }
+
+    fn author_exited(&self) {
+        let mut state = self.state.lock();
+        state.exited = true;
+        state.awake = false;
+        self.changed.notify_all();
+    }
+}
+
+impl Clock for StepClock {
+    fn now(&self) -> u64 {
+        self.state.lock().now
+    }
+
+    fn sleep(&self, duration: Duration) -> bool {
+        let mut state = self.state.lock();
+
+        if state.stopped {
+            return false;
+        }
+
+        state.wake_at = Some(state.now + duration.as_millis() as Time);
+        state.awake = false;
+        self.changed.notify_all();
+
+        while !state.awake {
+            if state.stopped {
+                return false;
+            }
+
+            self.changed.wait(&mut state);
+        }
+
+        true
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct NetworkConditions {
+    /// Minimum delay before a message is delivered.
+    pub min_delay: Time,
+    /// Maximum delay before a message is delivered.
+    pub max_delay: Time,
+    /// Probability of any message being dropped.
+    pub drop_rate: f64,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SimulationConfig {
+    pub nodes: usize,
+    /// The node holding the block authority key.
+    pub author: usize,
+    /// The node holding the finality authority key.
+    pub finality_validator: usize,
+    pub confirmation_depth: u32,
+    pub max_unfinalized_depth: Option<u32>,
+    pub network: NetworkConditions,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            nodes: 4,
+            author: 0,
+            finality_validator: 1,
+            confirmation_depth: 0,
+            max_unfinalized_depth: None,
+            network: NetworkConditions {
+                min_delay: 50,
+                max_delay: 500,
+                drop_rate: 0.0,
+            },
+        }
+    }
+}
+
+/// Observable events of a simulation run, used to compare runs with each other.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum TraceEvent {
+    Imported {
+        time: Time,
+        node: usize,
+        number: u64,
+    },
+    Finalized {
+        time: Time,
+        node: usize,
+        number: u64,
+    },
+    AuthoringPaused {
+        time: Time,
+        unfinalized_depth: u64,
+    },
+    AuthoringResumed {
+        time: Time,
+        unfinalized_depth: u64,
+    },
+    MessageDropped {
+        time: Time,
+        from: usize,
+        to: usize,
+    },
+    Stopped {
+        time: Time,
+        node: usize,
+    },
+    Restarted {
+        time: Time,
+        node: usize,
+    },
+}
+
+#[derive(Clone)]
+enum Message {
+    /// A sealed block header, announced by its author or after a partition heals.
+    Block(Header),
+    /// Request for the chain ending at `hash`, starting after block number `after`.
+    BlockRequest { hash: Hash, after: u64 },
+    /// Response to a `BlockRequest`, in ascending order.
+    Blocks(Vec<Header>),
+    /// An encoded `SingletonFinalityMessage`, as gossiped by the finality gadget.
+    Finality(Vec<u8>),
+}
+
+enum Event {
+    /// Starts the block author thread on the author node.
+    StartAuthor,
+    /// Wakes up the block author thread, which authors a block unless it pauses authoring.
+    AuthorWakeUp,
+    Deliver {
+        from: usize,
+        to: usize,
+        message: Message,
+    },
+    PartitionHealed(usize),
+    Stop(usize),
+    Restart(usize),
+}
+
+struct Partition {
+    nodes: HashSet<usize>,
+    from: Time,
+    until: Time,
+}
+
+impl Partition {
+    fn separates(&self, a: usize, b: usize, time: Time) -> bool {
+        self.from <= time && time < self.until && self.nodes.contains(&a) != self.nodes.contains(&b)
+    }
+}
+
+/// An in-memory chain of sealed headers.
+struct SimChain {
+    headers: HashMap<Hash, Header>,
+    genesis_hash: Hash,
+    best_hash: Hash,
+    finalized_hash: Hash,
+}
+
+impl SimChain {
+    fn new(genesis: Header) -> Self {
+        let genesis_hash = genesis.hash();
+        let mut headers = HashMap::new();
+        headers.insert(genesis_hash, genesis);
+
+        SimChain {
+            headers,
+            genesis_hash,
+            best_hash: genesis_hash,
+            finalized_hash: genesis_hash,
+        }
+    }
+
+    fn contains(&self, hash: &Hash) -> bool {
+        self.headers.contains_key(hash)
+    }
+
+    fn best_header(&self) -> &Header {
+        &self.headers[&self.best_hash]
+    }
+
+    fn best_number(&self) -> u64 {
+        *self.best_header().number()
+    }
+
+    fn finalized_number(&self) -> u64 {
+        *self.headers[&self.finalized_hash].number()
+    }
+
+    /// Imports a header whose parent is known, returns whether it became the new best block.
+    fn import(&mut self, header: Header) -> bool {
+        let hash = header.hash();
+        let is_new_best = *header.number() > self.best_number();
+
+        self.headers.insert(hash, header);
+
+        if is_new_best {
+            self.best_hash = hash;
+        }
+
+        is_new_best
+    }
+
+    /// Returns the chain ending at `hash` starting after block number `after`.
+    fn chain_to(&self, hash: Hash, after: u64) -> Vec<Header> {
+        let mut chain = Vec::new();
+        let mut current = self.headers.get(&hash);
+
+        while let Some(header) = current {
+            if *header.number() <= after {
+                break;
+            }
+
+            chain.push(header.clone());
+            current = self.headers.get(header.parent_hash());
+        }
+
+        chain.reverse();
+        chain
+    }
+}
+
+impl HeaderBackend<Block> for SimChain {
+    fn header(&self, id: BlockId<Block>) -> sp_blockchain::Result<Option<Header>> {
+        let hash = match id {
+            BlockId::Hash(hash) => Some(hash),
+            BlockId::Number(number) => self.hash(number)?,
+        };
+
+        Ok(hash.and_then(|hash| self.headers.get(&hash).cloned()))
+    }
+
+    fn info(&self) -> Info<Block> {
+        let parents = self
+            .headers
+            .values()
+            .map(|header| *header.parent_hash())
+            .collect::<HashSet<_>>();
+
+        Info {
+            best_hash: self.best_hash,
+            best_number: self.best_number(),
+            genesis_hash: self.genesis_hash,
+            finalized_hash: self.finalized_hash,
+            finalized_number: self.finalized_number(),
+            number_leaves: self
+                .headers
+                .keys()
+                .filter(|hash| !parents.contains(hash))
+                .count(),
+        }
+    }
+
+    fn status(&self, id: BlockId<Block>) -> sp_blockchain::Result<BlockStatus> {
+        Ok(match self.header(id)? {
+            Some(_) => BlockStatus::InChain,
+            None => BlockStatus::Unknown,
+        })
+    }
+
+    fn number(&self, hash: Hash) -> sp_blockchain::Result<Option<u64>> {
+        Ok(self.headers.get(&hash).map(|header| *header.number()))
+    }
+
+    fn hash(&self, number: u64) -> sp_blockchain::Result<Option<Hash>> {
+        let mut header = self.best_header();
+
+        if number > *header.number() {
+            return Ok(None);
+        }
+
+        while *header.number() > number {
+            header = &self.headers[header.parent_hash()];
+        }
+
+        Ok(Some(header.hash()))
+    }
+}
+
+impl HeaderMetadata<Block> for SimChain {
+    type Error = sp_blockchain::Error;
+
+    fn header_metadata(&self, hash: Hash) -> Result<CachedHeaderMetadata<Block>, Self::Error> {
+        self.headers
+            .get(&hash)
+            .map(CachedHeaderMetadata::from)
+            .ok_or_else(|| sp_blockchain::Error::UnknownBlock(format!("{:?}", hash)))
+    }
+
+    fn insert_header_metadata(&self, _hash: Hash, _metadata: CachedHeaderMetadata<Block>) {}
+
+    fn remove_header_metadata(&self, _hash: Hash) {}
+}
+
+/// A node's chain and aux storage, shared with the block author thread on the author node.
+struct SimClient {
+    chain: Mutex<SimChain>,
+    aux: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+    runtime_api: SimRuntimeApi,
+}
+
+impl HeaderBackend<Block> for SimClient {
+    fn header(&self, id: BlockId<Block>) -> sp_blockchain::Result<Option<Header>> {
+        self.chain.lock().header(id)
+    }
+
+    fn info(&self) -> Info<Block> {
+        self.chain.lock().info()
+    }
+
+    fn status(&self, id: BlockId<Block>) -> sp_blockchain::Result<BlockStatus> {
+        self.chain.lock().status(id)
+    }
+
+    fn number(&self, hash: Hash) -> sp_blockchain::Result<Option<u64>> {
+        self.chain.lock().number(hash)
+    }
+
+    fn hash(&self, number: u64) -> sp_blockchain::Result<Option<Hash>> {
+        self.chain.lock().hash(number)
+    }
+}
+
+impl HeaderMetadata<Block> for SimClient {
+    type Error = sp_blockchain::Error;
+
+    fn header_metadata(&self, hash: Hash) -> Result<CachedHeaderMetadata<Block>, Self::Error> {
+        self.chain.lock().header_metadata(hash)
+    }
+
+    fn insert_header_metadata(&self, _hash: Hash, _metadata: CachedHeaderMetadata<Block>) {}
+
+    fn remove_header_metadata(&self, _hash: Hash) {}
+}
+
+/// Finalizes blocks in memory, so that the finality gadget's `apply_finality_proof` can be used
+/// as is. Justifications aren't stored.
+impl Finalizer<Block, Backend> for SimClient {
+    fn apply_finality(
+        &self,
+        _operation: &mut ClientImportOperation<Block, Backend>,
+        id: BlockId<Block>,
+        justification: Option<Justification>,
+        notify: bool,
+    ) -> sp_blockchain::Result<()> {
+        self.finalize_block(id, justification, notify)
+    }
+
+    fn finalize_block(
+        &self,
+        id: BlockId<Block>,
+        _justification: Option<Justification>,
+        _notify: bool,
+    ) -> sp_blockchain::Result<()> {
+        let mut chain = self.chain.lock();
+
+        chain.finalized_hash = match id {
+            BlockId::Hash(hash) => hash,
+            BlockId::Number(number) => chain
+                .hash(number)?
+                .ok_or_else(|| sp_blockchain::Error::UnknownBlock(format!("{}", number)))?,
+        };
+
+        Ok(())
+    }
+}
+
+impl AuxStore for SimClient {
+    fn insert_aux<
+        'a,
+        'b: 'a,
+        'c: 'a,
+        I: IntoIterator<Item = &'a (&'c [u8], &'c [u8])>,
+        D: IntoIterator<Item = &'a &'b [u8]>,
+    >(
+        &self,
+        insert: I,
+        delete: D,
+    ) -> sp_blockchain::Result<()> {
+        let mut aux = self.aux.lock();
+
+        for (key, value) in insert {
+            aux.insert(key.to_vec(), value.to_vec());
+        }
+
+        for key in delete {
+            aux.remove(*key);
+        }
+
+        Ok(())
+    }
+
+    fn get_aux(&self, key: &[u8]) -> sp_blockchain::Result<Option<Vec<u8>>> {
+        Ok(self.aux.lock().get(key).cloned())
+    }
+}
+
+impl ProvideRuntimeApi<Block> for SimClient {
+    type Api = SimRuntimeApi;
+
+    fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+        self.runtime_api.clone().into()
+    }
+}
+
+/// Runtime of the simulated chain, with a single block authority and fixed leader election.
+#[derive(Clone)]
+struct SimRuntimeApi {
+    block_authority: AuthorityId,
+    finality_authority: AuthorityId,
+}
+
+sp_api::mock_impl_runtime_apis! {
+    impl SingletonApi<Block, AuthorityId> for SimRuntimeApi {
+        type Error = sp_blockchain::Error;
+
+        fn signature_scheme() -> SignatureScheme {
+            consensus_primitives::ed25519::SIGNATURE_SCHEME
+        }
+
+        fn leader_election() -> LeaderElection {
+            LeaderElection::Fixed
+        }
+
+        fn block_time() -> u64 {
+            BLOCK_TIME.as_millis() as u64
+        }
+
+        fn epoch_randomness() -> Randomness {
+            Randomness::default()
+        }
+
+        fn pow_difficulty() -> Option<u128> {
+            None
+        }
+
+        fn authorities(&self) -> Vec<AuthorityId> {
+            vec![self.block_authority.clone()]
+        }
+
+        fn finality_authority(&self) -> Option<AuthorityId> {
+            Some(self.finality_authority.clone())
+        }
+
+        fn next_authorities(&self) -> Vec<AuthorityId> {
+            vec![self.block_authority.clone()]
+        }
+
+        fn next_finality_authority(&self) -> Option<AuthorityId> {
+            Some(self.finality_authority.clone())
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SimSelectChain(Arc<SimClient>);
+
+impl SelectChainT<Block> for SimSelectChain {
+    fn leaves(&self) -> Result<Vec<Hash>, ConsensusError> {
+        Ok(vec![self.0.chain.lock().best_hash])
+    }
+
+    fn best_chain(&self) -> Result<Header, ConsensusError> {
+        Ok(self.0.chain.lock().best_header().clone())
+    }
+}
+
+/// Proposes empty blocks, as the simulation only keeps track of headers.
+struct SimEnvironment(Arc<StepClock>);
+
+impl Drop for SimEnvironment {
+    /// The environment is owned by the block author thread, so it is dropped when the thread
+    /// exits, including when it panics.
+    fn drop(&mut self) {
+        self.0.author_exited();
+    }
+}
+
+struct SimProposer {
+    parent_hash: Hash,
+    number: u64,
+}
+
+impl EnvironmentT<Block> for SimEnvironment {
+    type Proposer = SimProposer;
+    type CreateProposer = future::Ready<Result<SimProposer, ConsensusError>>;
+    type Error = ConsensusError;
+
+    fn init(&mut self, parent_header: &Header) -> Self::CreateProposer {
+        future::ready(Ok(SimProposer {
+            parent_hash: parent_header.hash(),
+            number: parent_header.number() + 1,
+        }))
+    }
+}
+
+impl Proposer<Block> for SimProposer {
+    type Error = ConsensusError;
+    type Transaction = TransactionFor<SimClient, Block>;
+    type Proposal = future::Ready<Result<Proposal<Block, Self::Transaction>, ConsensusError>>;
+
+    fn propose(
+        self,
+        _inherent_data: InherentData,
+        inherent_digests: DigestFor<Block>,
+        _max_duration: Duration,
+        _record_proof: RecordProof,
+    ) -> Self::Proposal {
+        let header = Header::new(
+            self.number,
+            Default::default(),
+            Default::default(),
+            self.parent_hash,
+            inherent_digests,
+        );
+
+        future::ready(Ok(Proposal {
+            block: Block {
+                header,
+                extrinsics: Vec::new(),
+            },
+            proof: None,
+            storage_changes: Default::default(),
+        }))
+    }
+}
+
+/// Collects the blocks sealed by the block author, which the simulation then imports and
+/// announces like any other block.
+#[derive(Clone, Default)]
+struct SimBlockImport(Arc<Mutex<Vec<Header>>>);
+
+impl BlockImport<Block> for SimBlockImport {
+    type Error = ConsensusError;
+    type Transaction = TransactionFor<SimClient, Block>;
+
+    fn check_block(
+        &mut self,
+        _block: BlockCheckParams<Block>,
+    ) -> Result<ImportResult, Self::Error> {
+        Ok(ImportResult::imported(false))
+    }
+
+    fn import_block(
+        &mut self,
+        block: BlockImportParams<Block, Self::Transaction>,
+        _new_cache: HashMap<CacheKeyId, Vec<u8>>,
+    ) -> Result<ImportResult, Self::Error> {
+        let mut header = block.header;
+
+        for item in block.post_digests {
+            header.digest_mut().push(item);
+        }
+
+        self.0.lock().push(header);
+        Ok(ImportResult::imported(true))
+    }
+}
+
+struct SimNode {
+    client: Arc<SimClient>,
+    /// Headers whose parent hasn't been imported yet, keyed by parent hash.
+    orphans: HashMap<Hash, Vec<Header>>,
+    pending_justifications:
+        PendingJustifications<Hash, SingletonFinalityJustification<AuthoritySignature>>,
+    finality_key: Option<AuthorityPair>,
+    /// Set while the node is stopped, during which messages sent to it are lost.
+    stopped: bool,
+}
+
+pub(crate) struct Simulation {
+    config: SimulationConfig,
+    rng: StdRng,
+    now: Time,
+    next_event_id: u64,
+    events: BTreeMap<(Time, u64), Event>,
+    nodes: Vec<SimNode>,
+    partitions: Vec<Partition>,
+    author_key: AuthorityPair,
+    author_clock: Arc<StepClock>,
+    /// The block author thread, once started.
+    author: Option<thread::JoinHandle<()>>,
+    authored: SimBlockImport,
+    authoring_stalled: bool,
+    verifier: HeaderVerifier<Block, AuthorityPair>,
+    trace: Vec<TraceEvent>,
+}
+
+fn authority_pair(seed: &str) -> AuthorityPair {
+    AuthorityPair::from_string(&format!("//{}", seed), None).expect("static values are valid; qed")
+}
+
+impl Simulation {
+    pub(crate) fn new(seed: u64, config: SimulationConfig) -> Self {
+        // ed25519 signatures are deterministic, so every run with the same seed produces the
+        // same seals and therefore the same block hashes.
+        let author_key = authority_pair("Alice");
+        let finality_key = authority_pair("Bob");
+
+        let genesis = Header::new(
+            0,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+
+        let runtime_api = SimRuntimeApi {
+            block_authority: author_key.public(),
+            finality_authority: finality_key.public(),
+        };
+
+        let nodes = (0..config.nodes)
+            .map(|node| SimNode {
+                client: Arc::new(SimClient {
+                    chain: Mutex::new(SimChain::new(genesis.clone())),
+                    aux: Mutex::new(HashMap::new()),
+                    runtime_api: runtime_api.clone(),
+                }),
+                orphans: HashMap::new(),
+                pending_justifications: PendingJustifications::new(
+                    MAX_PENDING_JUSTIFICATIONS,
+                    PENDING_JUSTIFICATION_TIMEOUT,
+                ),
+                finality_key: if node == config.finality_validator {
+                    Some(finality_key.clone())
+                } else {
+                    None
+                },
+                stopped: false,
+            })
+            .collect();
+
+        let mut simulation = Simulation {
+            rng: StdRng::seed_from_u64(seed),
+            now: 0,
+            next_event_id: 0,
+            events: BTreeMap::new(),
+            nodes,
+            partitions: Vec::new(),
+            verifier: HeaderVerifier {
+                authorities: vec![author_key.public().into()],
+                leader_election: LeaderElection::Fixed,
+                block_time: BLOCK_TIME,
+                failover_delay: BLOCK_TIME * FAILOVER_BLOCKS,
+                pow_difficulty: None,
+                allowed_engines: Vec::new(),
+                _phantom: PhantomData,
+            },
+            author_key,
+            author_clock: Arc::new(StepClock::new(0)),
+            author: None,
+            authored: SimBlockImport::default(),
+            authoring_stalled: false,
+            trace: Vec::new(),
+            config,
+        };
+
+        simulation.schedule(BLOCK_TIME.as_millis() as Time, Event::StartAuthor);
+        simulation
+    }
+
+    /// Isolates the given nodes from all other nodes between `from` and `until`.
+    pub(crate) fn partition(&mut self, nodes: &[usize], from: Time, until: Time) {
+        self.partitions.push(Partition {
+            nodes: nodes.iter().cloned().collect(),
+            from,
+            until,
+        });
+
+        let index = self.partitions.len() - 1;
+        self.schedule(
+            until.saturating_sub(self.now),
+            Event::PartitionHealed(index),
+        );
+    }
+
+    /// Stops the given node at `from` and restarts it at `until` on the same database: its chain
+    /// and aux storage (e.g. the signing history of the block author) are kept, while its orphans,
+    /// pending finality proofs and the messages sent to it in the meantime are lost.
+    pub(crate) fn restart(&mut self, node: usize, from: Time, until: Time) {
+        self.schedule(from.saturating_sub(self.now), Event::Stop(node));
+        self.schedule(until.saturating_sub(self.now), Event::Restart(node));
+    }
+
+    /// Processes all events scheduled up to (and including) the given time.
+    pub(crate) fn run_until(&mut self, time: Time) {
+        while let Some(&(at, id)) = self.events.keys().next() {
+            if at > time {
+                break;
+            }
+
+            let event = self
+                .events
+                .remove(&(at, id))
+                .expect("key was just read from the map; qed");
+
+            self.now = at;
+            self.handle(event);
+        }
+
+        self.now = time;
+    }
+
+    pub(crate) fn best_number(&self, node: usize) -> u64 {
+        self.nodes[node].client.chain.lock().best_number()
+    }
+
+    pub(crate) fn finalized_number(&self, node: usize) -> u64 {
+        self.nodes[node].client.chain.lock().finalized_number()
+    }
+
+    pub(crate) fn trace(&self) -> &[TraceEvent] {
+        &self.trace
+    }
+
+    fn schedule(&mut self, delay: Time, event: Event) {
+        let id = self.next_event_id;
+        self.next_event_id += 1;
+        self.events.insert((self.now + delay, id), event);
+    }
+
+    fn send(&mut self, from: usize, to: usize, message: Message) {
+        let now = self.now;
+        let partitioned = self
+            .partitions
+            .iter()
+            .any(|partition| partition.separates(from, to, now));
+
+        if partitioned || self.rng.gen_bool(self.config.network.drop_rate) {
+            self.trace.push(TraceEvent::MessageDropped {
+                time: now,
+                from,
+                to,
+            });
+            return;
+        }
+
+        let network = &self.config.network;
+        let delay = self.rng.gen_range(network.min_delay, network.max_delay + 1);
+
+        self.schedule(delay, Event::Deliver { from, to, message });
+    }
+
+    fn broadcast(&mut self, from: usize, message: Message) {
+        for to in 0..self.nodes.len() {
+            if to != from {
+                self.send(from, to, message.clone());
+            }
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::StartAuthor => {
+                // a restart may have started the author already.
+                if self.author.is_none() && !self.nodes[self.config.author].stopped {
+                    self.start_author();
+                }
+            }
+            Event::AuthorWakeUp => {
+                let unfinalized_depth = self.unfinalized_depth(self.config.author);
+
+                self.author_clock.advance_to(self.now);
+                self.on_author_asleep(unfinalized_depth);
+            }
+            Event::Deliver { from, to, message } => self.deliver(from, to, message),
+            Event::PartitionHealed(index) => {
+                // peers exchange their best blocks when they reconnect, which allows both sides
+                // to sync whatever they missed.
+                let isolated = self.partitions[index].nodes.clone();
+
+                for a in 0..self.nodes.len() {
+                    for b in 0..self.nodes.len() {
+                        if isolated.contains(&a) != isolated.contains(&b) {
+                            self.send_best_block(a, b);
+                        }
+                    }
+                }
+            }
+            Event::Stop(node) => self.stop(node),
+            Event::Restart(node) => {
+                self.nodes[node].stopped = false;
+                self.trace.push(TraceEvent::Restarted {
+                    time: self.now,
+                    node,
+                });
+
+                if node == self.config.author && self.author.is_none() {
+                    self.author_clock = Arc::new(StepClock::new(self.now));
+                    self.start_author();
+                }
+
+                // the restarted node reconnects to its peers, which exchange their best blocks.
+                for peer in 0..self.nodes.len() {
+                    if peer != node {
+                        self.send_best_block(node, peer);
+                        self.send_best_block(peer, node);
+                    }
+                }
+            }
+        }
+    }
+
+    fn send_best_block(&mut self, from: usize, to: usize) {
+        let best = self.nodes[from].client.chain.lock().best_header().clone();
+        self.send(from, to, Message::Block(best));
+    }
+
+    /// Stops the given node, dropping everything it only kept in memory. Stopping the author node
+    /// stops the block author thread.
+    fn stop(&mut self, node: usize) {
+        self.nodes[node].stopped = true;
+        self.nodes[node].orphans.clear();
+        self.nodes[node].pending_justifications =
+            PendingJustifications::new(MAX_PENDING_JUSTIFICATIONS, PENDING_JUSTIFICATION_TIMEOUT);
+        self.trace.push(TraceEvent::Stopped {
+            time: self.now,
+            node,
+        });
+
+        if node != self.config.author {
+            return;
+        }
+
+        self.author_clock.stop();
+        if let Some(author) = self.author.take() {
+            if let Err(err) = author.join() {
+                panic::resume_unwind(err);
+            }
+        }
+
+        let wake_ups = self
+            .events
+            .iter()
+            .filter(|(_, event)| matches!(event, Event::AuthorWakeUp))
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+
+        for key in wake_ups {
+            self.events.remove(&key);
+        }
+    }
+
+    /// Starts the block author thread on the author node, which authors its first block right
+    /// away.
+    fn start_author(&mut self) {
+        let author = self.config.author;
+        let client = self.nodes[author].client.clone();
+        let unfinalized_depth = self.unfinalized_depth(author);
+
+        self.author_clock.advance_to(self.now);
+
+        self.author = Some(
+            start_singleton_block_author(
+                runtime_config_at(client.clone(), Vec::new()),
+                self.author_key.clone().into(),
+                self.config.max_unfinalized_depth,
+                self.authored.clone(),
+                client.clone(),
+                SimEnvironment(self.author_clock.clone()),
+                SimSelectChain(client),
+                NoNetwork,
+                InherentDataProviders::new(),
+                None,
+                self.author_clock.clone(),
+            )
+            .expect("the simulated runtime has a block time; qed"),
+        );
+
+        self.on_author_asleep(unfinalized_depth);
+    }
+
+    /// Imports and announces the block sealed by the block author, if any, and schedules its
+    /// next wake up.
+    fn on_author_asleep(&mut self, unfinalized_depth: u64) {
+        let wake_at = self.wait_for_author();
+        let author = self.config.author;
+        let authored = std::mem::take(&mut *self.authored.0.lock());
+
+        // the author only skips its turn while it pauses authoring.
+        let stalled = authored.is_empty();
+        if stalled != self.authoring_stalled {
+            self.trace.push(if stalled {
+                TraceEvent::AuthoringPaused {
+                    time: self.now,
+                    unfinalized_depth,
+                }
+            } else {
+                TraceEvent::AuthoringResumed {
+                    time: self.now,
+                    unfinalized_depth,
+                }
+            });
+        }
+
+        self.authoring_stalled = stalled;
+
+        for header in authored {
+            self.import_blocks(author, vec![header.clone()]);
+            self.broadcast(author, Message::Block(header));
+        }
+
+        self.schedule(wake_at - self.now, Event::AuthorWakeUp);
+    }
+
+    /// Waits for the block author to go back to sleep and returns the time it sleeps until. If
+    /// the author thread panicked instead, the panic is propagated to the test.
+    fn wait_for_author(&mut self) -> Time {
+        if let Some(wake_at) = self.author_clock.wait_asleep() {
+            return wake_at;
+        }
+
+        let author = self
+            .author
+            .take()
+            .expect("the author exits only after being started; qed");
+
+        match author.join() {
+            Err(err) => panic::resume_unwind(err),
+            Ok(()) => panic!("Block author thread exited before the simulation was dropped"),
+        }
+    }
+
+    fn unfinalized_depth(&self, node: usize) -> u64 {
+        let chain = self.nodes[node].client.chain.lock();
+        chain.best_number() - chain.finalized_number()
+    }
+
+    fn deliver(&mut self, from: usize, to: usize, message: Message) {
+        if self.nodes[to].stopped {
+            self.trace.push(TraceEvent::MessageDropped {
+                time: self.now,
+                from,
+                to,
+            });
+            return;
+        }
+
+        match message {
+            Message::Block(header) => {
+                let parent_hash = *header.parent_hash();
+                let request_after = {
+                    let chain = self.nodes[to].client.chain.lock();
+
+                    if !chain.contains(&header.hash()) && !chain.contains(&parent_hash) {
+                        Some(chain.finalized_number())
+                    } else {
+                        None
+                    }
+                };
+
+                if let Some(after) = request_after {
+                    self.send(
+                        to,
+                        from,
+                        Message::BlockRequest {
+                            hash: parent_hash,
+                            after,
+                        },
+                    );
+                }
+
+                self.import_blocks(to, vec![header]);
+            }
+            Message::BlockRequest { hash, after } => {
+                let chain = self.nodes[to].client.chain.lock().chain_to(hash, after);
+
+                if !chain.is_empty() {
+                    self.send(to, from, Message::Blocks(chain));
+                }
+            }
+            Message::Blocks(headers) => self.import_blocks(to, headers),
+            Message::Finality(encoded) => self.on_finality_message(to, encoded),
+        }
+    }
+
+    /// Imports the given headers (and any orphans waiting on them), then lets the finality
+    /// validator act on the new best block once for the whole batch.
+    fn import_blocks(&mut self, node_id: usize, headers: Vec<Header>) {
+        let mut queue = headers;
+        let mut new_best = false;
+        let mut index = 0;
+
+        while index < queue.len() {
+            let header = queue[index].clone();
+            index += 1;
+
+            let hash = header.hash();
+            let node = &mut self.nodes[node_id];
+            let mut chain = node.client.chain.lock();
+
+            if chain.contains(&hash) {
+                continue;
+            }
+
+            if !chain.contains(header.parent_hash()) {
+                node.orphans
+                    .entry(*header.parent_hash())
+                    .or_insert_with(Vec::new)
+                    .push(header);
+                continue;
+            }
+
+            let parent_timestamp = chain
+                .header(BlockId::Hash(*header.parent_hash()))
+                .ok()
+                .flatten()
+                .map(|parent| header_timestamp(&parent))
+                .unwrap_or(0);
+
+            if self
+                .verifier
+                .check_header(&mut header.clone(), parent_timestamp, self.now)
+                .is_err()
+            {
+                continue;
+            }
+
+            let number = *header.number();
+            new_best |= chain.import(header);
+            drop(chain);
+
+            if let Some(orphans) = node.orphans.remove(&hash) {
+                queue.extend(orphans);
+            }
+
+            let pending = node.pending_justifications.take(&hash, self.now);
+
+            self.trace.push(TraceEvent::Imported {
+                time: self.now,
+                node: node_id,
+                number,
+            });
+
+            if let Some(proof) = pending {
+                self.finalize(node_id, hash, proof);
+            }
+        }
+
+        if new_best {
+            self.finalize_as_validator(node_id);
+        }
+    }
+
+    /// Checks a gossiped finality message like the finality gadget does, then finalizes its
+    /// block or keeps the proof until the block is imported.
+    fn on_finality_message(&mut self, node_id: usize, encoded: Vec<u8>) {
+        let client = self.nodes[node_id].client.clone();
+        let config_at = runtime_config_at(client.clone(), Vec::new());
+
+        let message = match check_finality_message::<Hash, AuthorityPair, _>(&encoded, |hash| {
+            finality_authority_of(&*client, &config_at, *hash)
+        }) {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+
+        if client.chain.lock().contains(&message.block_hash) {
+            self.finalize(node_id, message.block_hash, message.proof);
+        } else {
+            self.nodes[node_id].pending_justifications.insert(
+                message.block_hash,
+                message.proof,
+                self.now,
+            );
+        }
+    }
+
+    fn finalize_as_validator(&mut self, node_id: usize) {
+        let confirmation_depth = self.config.confirmation_depth;
+        let node = &self.nodes[node_id];
+
+        let key = match node.finality_key.as_ref() {
+            Some(key) => key,
+            None => return,
+        };
+
+        let target_hash = match finality_target::<Block, _>(&*node.client, confirmation_depth) {
+            Ok(Some(target_hash)) => target_hash,
+            _ => return,
+        };
+
+        let message = SingletonFinalityMessage {
+            block_hash: target_hash,
+            proof: SingletonFinalityJustification(key.sign(target_hash.as_ref())),
+        };
+
+        let encoded = message.encode();
+        self.finalize(node_id, target_hash, message.proof);
+        self.broadcast(node_id, Message::Finality(encoded));
+    }
+
+    /// Finalizes the given block with the finality gadget's `apply_finality_proof`.
+    fn finalize(
+        &mut self,
+        node_id: usize,
+        hash: Hash,
+        proof: SingletonFinalityJustification<AuthoritySignature>,
+    ) {
+        let client = self.nodes[node_id].client.clone();
+        let finalized = client.chain.lock().finalized_number();
+
+        apply_finality_proof::<Block, Backend, _, _>(&*client, hash, proof);
+
+        let number = client.chain.lock().finalized_number();
+        if number != finalized {
+            self.trace.push(TraceEvent::Finalized {
+                time: self.now,
+                node: node_id,
+                number,
+            });
+        }
+    }
+}
+
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        self.author_clock.stop();
+
+        // a panic of the author thread has already been propagated by `wait_for_author`.
+        if let Some(author) = self.author.take() {
+            let _ = author.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_TIME: Time = super::BLOCK_TIME.as_millis() as Time;
+
+    fn all_nodes(simulation: &Simulation, nodes: usize, best: u64, finalized: u64) -> bool {
+        (0..nodes).all(|node| {
+            simulation.best_number(node) == best && simulation.finalized_number(node) == finalized
+        })
+    }
+
+    #[test]
+    fn finalizes_all_blocks_without_faults() {
+        let mut simulation = Simulation::new(1, SimulationConfig::default());
+
+        simulation.run_until(20 * BLOCK_TIME + BLOCK_TIME / 2);
+
+        assert!(all_nodes(&simulation, 4, 20, 20));
+    }
+
+    #[test]
+    fn confirmation_depth_keeps_finality_behind_best_block() {
+        let config = SimulationConfig {
+            confirmation_depth: 3,
+            ..Default::default()
+        };
+        let mut simulation = Simulation::new(1, config);
+
+        simulation.run_until(20 * BLOCK_TIME + BLOCK_TIME / 2);
+
+        assert!(all_nodes(&simulation, 4, 20, 17));
+    }
+
+    #[test]
+    fn finality_validator_partitioned_for_50_blocks() {
+        let mut simulation = Simulation::new(42, SimulationConfig::default());
+
+        // isolate the finality validator right after block 10 has been finalized.
+        let from = 10 * BLOCK_TIME + BLOCK_TIME / 2;
+        let until = from + 50 * BLOCK_TIME;
+        simulation.partition(&[1], from, until);
+
+        simulation.run_until(until - 1);
+
+        assert_eq!(simulation.best_number(0), 60);
+        assert_eq!(simulation.finalized_number(0), 10);
+        assert_eq!(simulation.best_number(1), 10);
+
+        simulation.run_until(until + 5 * BLOCK_TIME);
+
+        assert!(all_nodes(&simulation, 4, 65, 65));
+    }
+
+    #[test]
+    fn author_pauses_while_finality_validator_is_partitioned() {
+        let config = SimulationConfig {
+            max_unfinalized_depth: Some(5),
+            ..Default::default()
+        };
+        let mut simulation = Simulation::new(7, config);
+
+        let from = 10 * BLOCK_TIME + BLOCK_TIME / 2;
+        let until = from + 50 * BLOCK_TIME;
+        simulation.partition(&[1], from, until);
+
+        simulation.run_until(until - 1);
+
+        assert_eq!(simulation.best_number(0), 16);
+        assert_eq!(simulation.finalized_number(0), 10);
+        assert!(simulation
+            .trace()
+            .iter()
+            .any(|event| matches!(event, TraceEvent::AuthoringPaused { .. })));
+
+        simulation.run_until(until + 5 * BLOCK_TIME);
+
+        assert!(simulation
+            .trace()
+            .iter()
+            .any(|event| matches!(event, TraceEvent::AuthoringResumed { .. })));
+
+        let best = simulation.best_number(0);
+        assert!(best > 16);
+        assert!(all_nodes(&simulation, 4, best, best));
+    }
+
+    #[test]
+    fn restarted_nodes_resume_authoring_and_finality() {
+        let mut simulation = Simulation::new(3, SimulationConfig::default());
+
+        // stop the author and the finality validator right after block 10 has been finalized,
+        // and restart them on their databases 5 block times later.
+        let from = 10 * BLOCK_TIME + BLOCK_TIME / 2;
+        let until = from + 5 * BLOCK_TIME;
+        simulation.restart(0, from, until);
+        simulation.restart(1, from, until);
+
+        simulation.run_until(until - 1);
+
+        assert!(all_nodes(&simulation, 4, 10, 10));
+
+        simulation.run_until(until + 5 * BLOCK_TIME + BLOCK_TIME / 4);
+
+        let best = simulation.best_number(0);
+        assert!(best > 10);
+        assert!(all_nodes(&simulation, 4, best, best));
+    }
+
+    #[test]
+    fn same_seed_replays_same_run() {
+        let run = |seed| {
+            let config = SimulationConfig {
+                confirmation_depth: 2,
+                network: NetworkConditions {
+                    min_delay: 10,
+                    max_delay: 4000,
+                    drop_rate: 0.2,
+                },
+                ..Default::default()
+            };
+
+            let mut simulation = Simulation::new(seed, config);
+            simulation.partition(&[1, 2], 20 * BLOCK_TIME, 40 * BLOCK_TIME);
+            simulation.run_until(60 * BLOCK_TIME);
+            simulation.trace().to_vec()
+        };
+
+        assert_eq!(run(1337), run(1337));
+    }
+}
@@ -0,0 +1,1293 @@
+use super::*;
+
+use std::task::Poll;
+
+use consensus_primitives::sr25519::AuthorityPair;
+use futures::executor::LocalPool;
+use futures::task::LocalSpawnExt;
+use sc_client_api::BlockBackend;
+use sc_network::config::{BoxFinalityProofRequestBuilder, ProtocolConfig};
+use sc_network_test::{BlockImportAdapter, Peer, PeersClient, PeersFullClient, TestNetFactory};
+use sp_consensus::import_queue::{BoxFinalityProofImport, BoxJustificationImport};
+use substrate_test_runtime_client::runtime::{Block, Hash, Header};
+
+type SingletonPeer = Peer<()>;
+
+const FINALITY_CONFIRMATION_DEPTH: u32 = 0;
+
+/// Engines whose pallets log authority changes in the node's runtime, which the node allows.
+const GRANDPA_ENGINE_ID: ConsensusEngineId = *b"FRNK";
+const AURA_ENGINE_ID: ConsensusEngineId = *b"aura";
+
+fn authority_pair(seed: &str) -> AuthorityPair {
+    AuthorityPair::from_string(&format!("//{}", seed), None).expect("static values are valid; qed")
+}
+
+fn singleton_config() -> SingletonConfig<AuthorityPair> {
+    SingletonConfig {
+        block_authority: authority_pair("Alice").public().into(),
+        backup_authorities: Vec::new(),
+        block_time: BLOCK_TIME,
+        failover_delay: BLOCK_TIME * FAILOVER_BLOCKS,
+        leader_election: LeaderElection::Fixed,
+        pow_difficulty: None,
+        finality_authority: authority_pair("Bob").public().into(),
+        allowed_engines: vec![GRANDPA_ENGINE_ID, AURA_ENGINE_ID],
+    }
+}
+
+/// Configuration of the second session of test networks with key rotation, in which other keys
+/// seal and finalize blocks.
+fn rotated_config() -> SingletonConfig<AuthorityPair> {
+    SingletonConfig {
+        block_authority: authority_pair("Charlie").public().into(),
+        finality_authority: authority_pair("Dave").public().into(),
+        ..singleton_config()
+    }
+}
+
+/// Looks up the configuration in effect at a block of the given peer's chain, which is
+/// `rotated_config` for the children of blocks from `rotation - 1` on.
+fn config_at(
+    client: Arc<PeersFullClient>,
+    rotation: Option<u64>,
+) -> ConfigAt<Block, AuthorityPair> {
+    Arc::new(move |at| {
+        let number = client
+            .block_number_from_id(at)
+            .map_err(Error::Client)?
+            .ok_or_else(|| Error::Client(sp_blockchain::Error::UnknownBlock(format!("{}", at))))?;
+
+        match rotation {
+            Some(rotation) if number + 1 >= rotation => Ok(rotated_config()),
+            _ => Ok(singleton_config()),
+        }
+    })
+}
+
+fn header_verifier(
+    config: &SingletonConfig<AuthorityPair>,
+) -> HeaderVerifier<Block, AuthorityPair> {
+    HeaderVerifier::new(config)
+}
+
+/// Adds the pre-runtime digest to the given header and seals it the same way
+/// `start_singleton_block_author` does.
+fn seal_header_as(
+    header: &mut Header,
+    key: &AuthorityPair,
+    authority_index: AuthorityIndex,
+    timestamp: u64,
+    vrf: Option<VrfSignature>,
+) {
+    header
+        .digest_mut()
+        .push(pre_runtime_digest(authority_index, timestamp, vrf));
+
+    let seal = SingletonSeal(key.sign(header.hash().as_ref()));
+    header.digest_mut().push(seal.into());
+}
+
+/// Seals the given header as the block authority.
+fn seal_header(header: &mut Header, key: &AuthorityPair) {
+    seal_header_as(header, key, BLOCK_AUTHORITY_INDEX, 0, None);
+}
+
+struct SingletonTestNet {
+    peers: Vec<SingletonPeer>,
+    /// Number of the first block sealed and finalized with the keys of `rotated_config`, if the
+    /// authorities rotate their keys.
+    rotation: Option<u64>,
+}
+
+impl SingletonTestNet {
+    fn with_rotation(peers: usize, rotation: u64) -> Self {
+        let mut net = SingletonTestNet {
+            peers: Vec::new(),
+            rotation: Some(rotation),
+        };
+
+        for _ in 0..peers {
+            net.add_full_peer();
+        }
+
+        net
+    }
+
+    fn config_at(&self, client: &PeersClient) -> ConfigAt<Block, AuthorityPair> {
+        let client = client
+            .as_full()
+            .expect("only full clients are used in tests; qed");
+
+        config_at(client, self.rotation)
+    }
+}
+
+impl TestNetFactory for SingletonTestNet {
+    type Verifier = SingletonVerifier<Block, PeersFullClient, AuthorityPair>;
+    type PeerData = ();
+
+    fn from_config(_config: &ProtocolConfig) -> Self {
+        SingletonTestNet {
+            peers: Vec::new(),
+            rotation: None,
+        }
+    }
+
+    fn make_verifier(
+        &self,
+        client: PeersClient,
+        _config: &ProtocolConfig,
+        _peer_data: &(),
+    ) -> Self::Verifier {
+        SingletonVerifier {
+            config_at: self.config_at(&client),
+            client: client
+                .as_full()
+                .expect("only full clients are used in tests; qed"),
+            epoch_randomness: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    fn make_block_import<Transaction>(
+        &self,
+        client: PeersClient,
+    ) -> (
+        BlockImportAdapter<Transaction>,
+        Option<BoxJustificationImport<Block>>,
+        Option<BoxFinalityProofImport<Block>>,
+        Option<BoxFinalityProofRequestBuilder<Block>>,
+        (),
+    ) {
+        let config_at = self.config_at(&client);
+        let client = client
+            .as_full()
+            .expect("only full clients are used in tests; qed");
+
+        let block_import = SingletonBlockImport::<_, _, _, AuthorityPair> {
+            inner: client.clone(),
+            client,
+            config_at: Some(config_at),
+        };
+
+        (
+            BlockImportAdapter::new_full(block_import),
+            None,
+            None,
+            None,
+            (),
+        )
+    }
+
+    fn peer(&mut self, i: usize) -> &mut SingletonPeer {
+        &mut self.peers[i]
+    }
+
+    fn peers(&self) -> &Vec<SingletonPeer> {
+        &self.peers
+    }
+
+    fn mut_peers<F: FnOnce(&mut Vec<SingletonPeer>)>(&mut self, closure: F) {
+        closure(&mut self.peers);
+    }
+}
+
+/// Spawns a finality gadget for the given peer, which also acts as the finality authority with
+/// the key of the given seed, if any.
+fn spawn_finality_gadget(
+    net: &Arc<Mutex<SingletonTestNet>>,
+    pool: &LocalPool,
+    peer_id: usize,
+    finality_key: Option<&str>,
+) {
+    let (config_at, client, network) = {
+        let net = net.lock();
+        let peer = &net.peers()[peer_id];
+        let client = peer
+            .client()
+            .as_full()
+            .expect("only full clients are used in tests; qed");
+
+        (
+            net.config_at(peer.client()),
+            client,
+            peer.network_service().clone(),
+        )
+    };
+
+    let authority_key =
+        finality_key.map(|seed| SingletonFinalityAuthorityPair::from(authority_pair(seed)));
+
+    let gadget = start_singleton_finality_gadget(
+        config_at,
+        authority_key,
+        FINALITY_CONFIRMATION_DEPTH,
+        client,
+        network.clone(),
+        network,
+    );
+
+    pool.spawner()
+        .spawn_local(gadget)
+        .expect("local pool is running; qed");
+}
+
+/// Spawns a finality gadget on every peer, with `validator` acting as the finality authority.
+fn spawn_finality_gadgets(net: &Arc<Mutex<SingletonTestNet>>, pool: &LocalPool, validator: usize) {
+    let peers = net.lock().peers().len();
+
+    for peer_id in 0..peers {
+        let finality_key = if peer_id == validator {
+            Some("Bob")
+        } else {
+            None
+        };
+
+        spawn_finality_gadget(net, pool, peer_id, finality_key);
+    }
+}
+
+/// Authors `count` blocks sealed by the block authority on top of the best block of the given
+/// peer.
+fn author_blocks(net: &Arc<Mutex<SingletonTestNet>>, author: usize, count: usize) {
+    author_blocks_as(net, author, count, "Alice");
+}
+
+/// Authors `count` blocks sealed with the key of the given seed on top of the best block of the
+/// given peer.
+fn author_blocks_as(net: &Arc<Mutex<SingletonTestNet>>, author: usize, count: usize, seed: &str) {
+    let key = authority_pair(seed);
+
+    net.lock()
+        .peer(author)
+        .generate_blocks(count, BlockOrigin::Own, |builder| {
+            let mut block = builder.build().expect("block builds; qed").block;
+            seal_header(&mut block.header, &key);
+            block
+        });
+}
+
+/// Builds a block on the best block of the given peer, seals it with `seal` and imports it as
+/// the new best block without verifying it, for blocks the verifier of the test network rejects.
+/// Returns the hash of the imported block.
+fn import_unverified<F>(net: &Arc<Mutex<SingletonTestNet>>, peer: usize, seal: F) -> Hash
+where
+    F: FnOnce(&mut Header),
+{
+    let mut net = net.lock();
+    let mut seal = Some(seal);
+    let mut unverified = None;
+
+    // blocks generated by a peer always go through its verifier, so a sibling sealed by the
+    // block authority is imported that way first.
+    net.peer(peer)
+        .generate_blocks(1, BlockOrigin::Own, |builder| {
+            let mut block = builder.build().expect("block builds; qed").block;
+            let (mut header, body) = block.clone().deconstruct();
+            (seal.take().expect("a single block is built; qed"))(&mut header);
+            unverified = Some((header, body));
+
+            seal_header(&mut block.header, &authority_pair("Alice"));
+            block
+        });
+
+    let (header, body) = unverified.expect("block was built; qed");
+    let post_hash = header.hash();
+    let client = net.peers()[peer]
+        .client()
+        .as_full()
+        .expect("only full clients are used in tests; qed");
+
+    let mut import_params = BlockImportParams::new(BlockOrigin::File, header);
+    if let Some(seal) = import_params.header.digest_mut().pop() {
+        import_params.post_digests.push(seal);
+    }
+    import_params.body = Some(body);
+    import_params.fork_choice = Some(ForkChoiceStrategy::Custom(true));
+    (&*client)
+        .import_block(import_params, HashMap::default())
+        .expect("block imports; qed");
+
+    post_hash
+}
+
+/// Drives the network until the given condition holds on it.
+fn run_until<F>(net: &Arc<Mutex<SingletonTestNet>>, pool: &mut LocalPool, mut condition: F)
+where
+    F: FnMut(&mut SingletonTestNet) -> bool,
+{
+    let net = net.clone();
+
+    pool.run_until(future::poll_fn(move |cx| {
+        let mut net = net.lock();
+        net.poll(cx);
+
+        if condition(&mut net) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+}
+
+fn all_peers_at(net: &SingletonTestNet, best: u64, finalized: u64) -> bool {
+    net.peers().iter().all(|peer| {
+        let info = peer.client().info();
+        info.best_number == best && info.finalized_number == finalized
+    })
+}
+
+fn assert_sealed_by_block_authority(peer: &SingletonPeer, blocks: u64) {
+    let verifier = header_verifier(&singleton_config());
+
+    for number in 1..=blocks {
+        let mut header = peer
+            .client()
+            .header(&BlockId::Number(number))
+            .unwrap()
+            .expect("block was imported; qed");
+
+        assert!(verifier.check_header(&mut header, 0, 0).is_ok());
+    }
+}
+
+#[test]
+fn authored_blocks_are_imported_and_finalized_by_all_peers() {
+    let _ = env_logger::try_init();
+
+    let net = Arc::new(Mutex::new(SingletonTestNet::new(4)));
+    let mut pool = LocalPool::new();
+
+    // peer 0 authors blocks, peer 1 finalizes them and the others follow.
+    spawn_finality_gadgets(&net, &pool, 1);
+    author_blocks(&net, 0, 10);
+
+    run_until(&net, &mut pool, |net| all_peers_at(net, 10, 10));
+
+    for peer in net.lock().peers() {
+        assert_sealed_by_block_authority(peer, 10);
+    }
+}
+
+#[test]
+fn imports_blocks_with_authority_changes_of_other_engines() {
+    let _ = env_logger::try_init();
+
+    let net = Arc::new(Mutex::new(SingletonTestNet::new(2)));
+    let mut pool = LocalPool::new();
+    let key = authority_pair("Alice");
+
+    spawn_finality_gadgets(&net, &pool, 1);
+
+    // the GRANDPA and Aura pallets log their new authorities at each session change.
+    net.lock()
+        .peer(0)
+        .generate_blocks(1, BlockOrigin::Own, |builder| {
+            let mut block = builder.build().expect("block builds; qed").block;
+            block
+                .header
+                .digest_mut()
+                .push(DigestItem::Consensus(GRANDPA_ENGINE_ID, vec![1, 2, 3]));
+            block
+                .header
+                .digest_mut()
+                .push(DigestItem::Consensus(AURA_ENGINE_ID, vec![4, 5, 6]));
+            seal_header(&mut block.header, &key);
+            block
+        });
+
+    run_until(&net, &mut pool, |net| all_peers_at(net, 1, 1));
+
+    let header = net.lock().peers()[1]
+        .client()
+        .header(&BlockId::Number(1))
+        .unwrap()
+        .expect("block was imported; qed");
+    assert!(header
+        .digest()
+        .logs()
+        .contains(&DigestItem::Consensus(GRANDPA_ENGINE_ID, vec![1, 2, 3])));
+}
+
+#[test]
+fn exports_justifications_of_finalized_blocks() {
+    let _ = env_logger::try_init();
+
+    let net = Arc::new(Mutex::new(SingletonTestNet::new(2)));
+    let mut pool = LocalPool::new();
+
+    spawn_finality_gadgets(&net, &pool, 1);
+    author_blocks(&net, 0, 5);
+
+    run_until(&net, &mut pool, |net| all_peers_at(net, 5, 5));
+
+    let client = net.lock().peers()[1]
+        .client()
+        .as_full()
+        .expect("only full clients are used in tests; qed");
+    let finality_authority = authority_pair("Bob").public();
+
+    let justifications = export_justifications::<Block, _, AuthorityPair>(&*client, 1, 10).unwrap();
+
+    // the last block is always finalized explicitly by the finality authority.
+    assert_eq!(justifications.last().map(|(number, _, _)| *number), Some(5));
+
+    for (number, hash, justification) in &justifications {
+        assert_eq!(client.hash(*number).unwrap(), Some(*hash));
+        assert!(AuthorityPair::verify(
+            justification.as_ref(),
+            hash,
+            &finality_authority
+        ));
+    }
+
+    assert!(
+        export_justifications::<Block, _, AuthorityPair>(&*client, 6, 10)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn imports_only_valid_justifications_of_finalizable_blocks() {
+    let _ = env_logger::try_init();
+
+    // no finality gadget runs, so that blocks are only finalized by imported justifications.
+    let net = Arc::new(Mutex::new(SingletonTestNet::new(2)));
+    let mut pool = LocalPool::new();
+    let key = authority_pair("Alice");
+
+    let author_fork = |peer: usize, count: usize, timestamp: u64| {
+        net.lock()
+            .peer(peer)
+            .generate_blocks(count, BlockOrigin::Own, |builder| {
+                let mut block = builder.build().expect("block builds; qed").block;
+                seal_header_as(
+                    &mut block.header,
+                    &key,
+                    BLOCK_AUTHORITY_INDEX,
+                    timestamp,
+                    None,
+                );
+                block
+            })
+    };
+
+    let client = net.lock().peers()[0]
+        .client()
+        .as_full()
+        .expect("only full clients are used in tests; qed");
+
+    // peer 0 authors three blocks, and peer 1 a longer fork which peer 0 imports too.
+    author_fork(0, 3, 1);
+    let own_fork = (1..=3)
+        .map(|number| {
+            client
+                .hash(number)
+                .unwrap()
+                .expect("block was authored; qed")
+        })
+        .collect::<Vec<_>>();
+    let other_fork = author_fork(1, 4, 2);
+
+    run_until(&net, &mut pool, |net| all_peers_at(net, 4, 0));
+
+    let config_at = config_at(client.clone(), None);
+    let import = |number: u64, hash: Hash, seed: &str| {
+        let justification =
+            SingletonFinalityJustification(authority_pair(seed).sign(hash.as_ref()));
+        import_justifications::<Block, _, _, AuthorityPair>(
+            &*client,
+            &config_at,
+            vec![(number, hash, justification)],
+        )
+    };
+
+    assert_eq!(
+        import(2, own_fork[1], "Bob").unwrap(),
+        ImportedJustifications {
+            finalized: 1,
+            skipped: 0
+        },
+    );
+    assert_eq!(client.info().finalized_hash, own_fork[1]);
+    assert!(client
+        .justification(&BlockId::Hash(own_fork[1]))
+        .unwrap()
+        .is_some());
+
+    // justifications of already finalized blocks are verified but skipped.
+    assert_eq!(
+        import(1, own_fork[0], "Bob").unwrap(),
+        ImportedJustifications {
+            finalized: 0,
+            skipped: 1
+        },
+    );
+
+    // only the finality authority's justifications are imported.
+    assert!(matches!(
+        import(3, own_fork[2], "Charlie"),
+        Err(Error::BadJustification(_))
+    ));
+
+    // and only for blocks descending from the last finalized block.
+    assert!(matches!(
+        import(4, other_fork, "Bob"),
+        Err(Error::Client(sp_blockchain::Error::NotInFinalizedChain))
+    ));
+
+    assert_eq!(client.info().finalized_hash, own_fork[1]);
+}
+
+/// Looks up epoch randomness for tests without VRF leader election, where it's never needed.
+fn no_epoch_randomness() -> EpochRandomness<Block> {
+    Box::new(|_| Err(Error::VrfUnsupported))
+}
+
+#[test]
+fn verify_chain_reports_first_block_with_invalid_seal() {
+    let net = Arc::new(Mutex::new(SingletonTestNet::new(1)));
+
+    let client = net.lock().peers()[0]
+        .client()
+        .as_full()
+        .expect("only full clients are used in tests; qed");
+
+    author_blocks(&net, 0, 2);
+    let invalid_hash = import_unverified(&net, 0, |header| {
+        seal_header(header, &authority_pair("Eve"))
+    });
+    author_blocks(&net, 0, 2);
+
+    let verification = verify_chain::<Block, _, AuthorityPair>(
+        &*client,
+        &config_at(client.clone(), None),
+        &no_epoch_randomness(),
+    )
+    .unwrap();
+
+    // the blocks after the invalid one aren't checked.
+    assert_eq!(verification.blocks, 2);
+    assert_eq!(verification.sealed.get(&BLOCK_AUTHORITY_INDEX), Some(&2));
+    assert!(matches!(
+        verification.first_invalid,
+        Some((3, hash, Error::Seal(SealError::BadSignature))) if hash == invalid_hash
+    ));
+}
+
+#[test]
+fn inspect_block_decodes_seals_and_justifications() {
+    const DIFFICULTY: u128 = 16;
+
+    let net = Arc::new(Mutex::new(SingletonTestNet::new(1)));
+
+    let client = net.lock().peers()[0]
+        .client()
+        .as_full()
+        .expect("only full clients are used in tests; qed");
+    let config_at: ConfigAt<Block, AuthorityPair> = Arc::new(|_| {
+        Ok(SingletonConfig {
+            pow_difficulty: Some(DIFFICULTY),
+            ..singleton_config()
+        })
+    });
+    let inspect = |number: u64| {
+        inspect_block::<Block, _, AuthorityPair>(
+            &*client,
+            BlockId::Number(number),
+            &config_at,
+            &no_epoch_randomness(),
+        )
+        .unwrap()
+    };
+
+    // block 1 is sealed by the block authority and finalized, block 2 is mined.
+    author_blocks(&net, 0, 1);
+    let timestamp = 5 * BLOCK_TIME.as_millis() as u64;
+    let mut nonce = None;
+    let mined_hash = import_unverified(&net, 0, |header| {
+        header.digest_mut().push(pow_pre_runtime_digest(timestamp));
+
+        let pre_hash = header.hash();
+        let mined = (0..)
+            .find(|nonce| meets_difficulty(&pow_hash(pre_hash.as_ref(), *nonce), DIFFICULTY))
+            .unwrap();
+        header.digest_mut().push(PowSeal { nonce: mined }.into());
+        nonce = Some(mined);
+    });
+
+    let authority_hash = client.hash(1).unwrap().expect("block was authored; qed");
+    let justification =
+        SingletonFinalityJustification(authority_pair("Bob").sign(authority_hash.as_ref()));
+    import_justifications::<Block, _, _, AuthorityPair>(
+        &*client,
+        &config_at,
+        vec![(1, authority_hash, justification)],
+    )
+    .unwrap();
+
+    let sealed = inspect(1);
+    assert_eq!(sealed.post_hash, authority_hash);
+    assert!(matches!(sealed.seal, Some(InspectedSeal::Authority(_))));
+    assert_eq!(
+        sealed
+            .pre_digest
+            .map(|pre_digest| pre_digest.authority_index),
+        Some(BLOCK_AUTHORITY_INDEX),
+    );
+    assert_eq!(sealed.authority, Some(authority_pair("Alice").public()));
+    assert!(matches!(sealed.seal_check, Some(Ok(()))));
+    assert!(matches!(
+        sealed.justification,
+        Some(InspectedJustification::Singleton(_, true))
+    ));
+
+    let mined = inspect(2);
+    assert_eq!(mined.post_hash, mined_hash);
+    assert!(matches!(mined.seal, Some(InspectedSeal::Work(work)) if Some(work) == nonce));
+    assert_eq!(
+        mined.pow_pre_digest.map(|pre_digest| pre_digest.timestamp),
+        Some(timestamp),
+    );
+    assert!(mined.pre_digest.is_none());
+    assert!(mined.authority.is_none());
+    assert!(matches!(mined.seal_check, Some(Ok(()))));
+    assert!(mined.justification.is_none());
+}
+
+#[test]
+fn late_joining_peer_syncs_finalized_chain() {
+    let _ = env_logger::try_init();
+
+    let net = Arc::new(Mutex::new(SingletonTestNet::new(3)));
+    let mut pool = LocalPool::new();
+
+    spawn_finality_gadgets(&net, &pool, 1);
+    author_blocks(&net, 0, 5);
+
+    run_until(&net, &mut pool, |net| all_peers_at(net, 5, 5));
+
+    // a peer joining with an empty database should import the authored blocks and their
+    // justifications while syncing. Restarts on the same database are covered by the
+    // simulation.
+    net.lock().add_full_peer();
+
+    run_until(&net, &mut pool, |net| all_peers_at(net, 5, 5));
+
+    assert_sealed_by_block_authority(&net.lock().peers()[3], 5);
+}
+
+#[test]
+fn finality_resumes_after_author_key_moves_to_new_peer() {
+    let _ = env_logger::try_init();
+
+    let net = Arc::new(Mutex::new(SingletonTestNet::new(3)));
+    let mut pool = LocalPool::new();
+
+    spawn_finality_gadgets(&net, &pool, 1);
+    author_blocks(&net, 0, 3);
+
+    run_until(&net, &mut pool, |net| all_peers_at(net, 3, 3));
+
+    // the author is replaced by a fresh peer which syncs the chain and keeps authoring on top
+    // of it with the same key.
+    net.lock().add_full_peer();
+    spawn_finality_gadget(&net, &pool, 3, None);
+
+    run_until(&net, &mut pool, |net| all_peers_at(net, 3, 3));
+
+    author_blocks(&net, 3, 3);
+
+    run_until(&net, &mut pool, |net| all_peers_at(net, 6, 6));
+}
+
+#[test]
+fn authorities_rotate_their_keys_at_session_change() {
+    let _ = env_logger::try_init();
+
+    // blocks from #4 on are sealed by Charlie instead of Alice and finalized by Dave instead of
+    // Bob, whose gadgets run on peers 1 and 2.
+    let net = Arc::new(Mutex::new(SingletonTestNet::with_rotation(4, 4)));
+    let mut pool = LocalPool::new();
+
+    spawn_finality_gadget(&net, &pool, 0, None);
+    spawn_finality_gadget(&net, &pool, 1, Some("Bob"));
+    spawn_finality_gadget(&net, &pool, 2, Some("Dave"));
+    spawn_finality_gadget(&net, &pool, 3, None);
+
+    author_blocks(&net, 0, 3);
+    run_until(&net, &mut pool, |net| all_peers_at(net, 3, 3));
+
+    author_blocks_as(&net, 0, 3, "Charlie");
+    run_until(&net, &mut pool, |net| all_peers_at(net, 6, 6));
+
+    let client = net.lock().peers()[3]
+        .client()
+        .as_full()
+        .expect("only full clients are used in tests; qed");
+
+    for (number, finality_key) in &[(3, "Bob"), (6, "Dave")] {
+        let hash = client
+            .hash(*number)
+            .unwrap()
+            .expect("block was imported; qed");
+        let justification = client
+            .justification(&BlockId::Hash(hash))
+            .unwrap()
+            .expect("the finality authority finalized the block explicitly; qed");
+        let justification =
+            SingletonFinalityJustification::<<AuthorityPair as Pair>::Signature>::decode(
+                &mut &justification[..],
+            )
+            .unwrap();
+
+        assert!(AuthorityPair::verify(
+            justification.as_ref(),
+            &hash,
+            &authority_pair(finality_key).public(),
+        ));
+    }
+
+    // the previous block authority can't seal blocks anymore.
+    let mut verifier = SingletonVerifier::<_, _, AuthorityPair> {
+        config_at: config_at(client.clone(), Some(4)),
+        client: client.clone(),
+        epoch_randomness: None,
+        clock: Arc::new(SystemClock),
+    };
+
+    let best_hash = client.info().best_hash;
+    let sealed_by = |seed: &str| {
+        let mut header = Header::new(
+            7,
+            Default::default(),
+            Default::default(),
+            best_hash,
+            Default::default(),
+        );
+        seal_header(&mut header, &authority_pair(seed));
+        header
+    };
+
+    assert!(verifier
+        .verify(
+            BlockOrigin::NetworkBroadcast,
+            sealed_by("Alice"),
+            None,
+            None
+        )
+        .is_err());
+    assert!(verifier
+        .verify(
+            BlockOrigin::NetworkBroadcast,
+            sealed_by("Charlie"),
+            None,
+            None
+        )
+        .is_ok());
+}
+
+#[test]
+fn rejects_blocks_sealed_by_unknown_authority() {
+    let verifier = header_verifier(&singleton_config());
+
+    let mut header = Header::new(
+        1,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+
+    assert_eq!(
+        verifier.check_header(&mut header.clone(), 0, 0).err(),
+        Some(SealError::Unsealed),
+    );
+
+    seal_header(&mut header, &authority_pair("Eve"));
+    assert_eq!(
+        verifier.check_header(&mut header.clone(), 0, 0).err(),
+        Some(SealError::BadSignature),
+    );
+}
+
+#[test]
+fn rejects_headers_with_invalid_digests() {
+    const OTHER_ENGINE_ID: ConsensusEngineId = *b"othr";
+
+    let mut verifier = header_verifier(&singleton_config());
+
+    let key = authority_pair("Alice");
+    let header = Header::new(
+        1,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+
+    let check = |verifier: &HeaderVerifier<Block, AuthorityPair>,
+                 extra: DigestItem<Hash>,
+                 trailing: Option<DigestItem<Hash>>| {
+        let mut header = header.clone();
+        header.digest_mut().push(extra);
+        seal_header(&mut header, &key);
+
+        if let Some(trailing) = trailing {
+            header.digest_mut().push(trailing);
+        }
+
+        verifier.check_header(&mut header, 0, 0).err()
+    };
+
+    let other_seal = DigestItem::Seal(OTHER_ENGINE_ID, vec![1, 2, 3]);
+    let other_pre_runtime = DigestItem::PreRuntime(OTHER_ENGINE_ID, vec![1, 2, 3]);
+    let singleton_seal = DigestItem::Seal(SINGLETON_ENGINE_ID, vec![1, 2, 3]);
+
+    // the singleton seal must be the last digest item.
+    assert_eq!(
+        check(
+            &verifier,
+            DigestItem::Other(vec![]),
+            Some(other_seal.clone())
+        ),
+        Some(SealError::WrongEngine(OTHER_ENGINE_ID)),
+    );
+
+    // and it must be decodable.
+    assert_eq!(
+        check(
+            &verifier,
+            DigestItem::Other(vec![]),
+            Some(singleton_seal.clone())
+        ),
+        Some(SealError::InvalidSeal),
+    );
+
+    // there can't be any other singleton seal before it.
+    assert_eq!(
+        check(&verifier, singleton_seal, None),
+        Some(SealError::DuplicateSeal),
+    );
+
+    // digest items of other engines are rejected unless explicitly allowed.
+    assert_eq!(
+        check(&verifier, other_seal.clone(), None),
+        Some(SealError::UnexpectedEngine(OTHER_ENGINE_ID)),
+    );
+    assert_eq!(
+        check(&verifier, other_pre_runtime.clone(), None),
+        Some(SealError::UnexpectedEngine(OTHER_ENGINE_ID)),
+    );
+
+    // the author must be identified by exactly one valid singleton pre-runtime digest.
+    assert_eq!(
+        check(
+            &verifier,
+            pre_runtime_digest(BLOCK_AUTHORITY_INDEX, 0, None),
+            None
+        ),
+        Some(SealError::DuplicatePreDigest),
+    );
+    assert_eq!(
+        check(&verifier, pre_runtime_digest(1, 0, None), None),
+        Some(SealError::DuplicatePreDigest),
+    );
+
+    let mut unidentified = header.clone();
+    let seal = SingletonSeal(key.sign(unidentified.hash().as_ref()));
+    unidentified.digest_mut().push(seal.into());
+    assert_eq!(
+        verifier.check_header(&mut unidentified, 0, 0).err(),
+        Some(SealError::MissingPreDigest),
+    );
+
+    let mut invalid = header.clone();
+    invalid
+        .digest_mut()
+        .push(DigestItem::PreRuntime(SINGLETON_ENGINE_ID, vec![1]));
+    let seal = SingletonSeal(key.sign(invalid.hash().as_ref()));
+    invalid.digest_mut().push(seal.into());
+    assert_eq!(
+        verifier.check_header(&mut invalid, 0, 0).err(),
+        Some(SealError::InvalidPreDigest),
+    );
+
+    // the authority claimed by the pre-runtime digest must be the one that sealed the block.
+    let mut unknown_authority = header.clone();
+    unknown_authority
+        .digest_mut()
+        .push(pre_runtime_digest(1, 0, None));
+    let seal = SingletonSeal(key.sign(unknown_authority.hash().as_ref()));
+    unknown_authority.digest_mut().push(seal.into());
+    assert_eq!(
+        verifier.check_header(&mut unknown_authority, 0, 0).err(),
+        Some(SealError::UnknownAuthority(1)),
+    );
+
+    verifier.allowed_engines.push(OTHER_ENGINE_ID);
+
+    assert_eq!(check(&verifier, other_seal, None), None);
+    assert_eq!(check(&verifier, other_pre_runtime, None), None);
+}
+
+#[test]
+fn backup_authorities_seal_only_after_failover_delay() {
+    let mut config = singleton_config();
+    config.backup_authorities = vec![
+        authority_pair("Charlie").public().into(),
+        authority_pair("Dave").public().into(),
+    ];
+
+    assert_eq!(
+        config
+            .block_authority_index(&authority_pair("Dave").public())
+            .ok(),
+        Some(2),
+    );
+    assert!(config
+        .block_authority_index(&authority_pair("Eve").public())
+        .is_err());
+
+    let verifier = header_verifier(&config);
+    let delay = failover_delay_millis(config.failover_delay, 1);
+    let parent_timestamp = 1_000;
+    let now = parent_timestamp + 10 * delay;
+
+    let header = Header::new(
+        2,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+
+    let check = |seed: &str, authority_index: AuthorityIndex, timestamp: u64| {
+        let mut header = header.clone();
+        seal_header_as(
+            &mut header,
+            &authority_pair(seed),
+            authority_index,
+            timestamp,
+            None,
+        );
+        verifier
+            .check_header(&mut header, parent_timestamp, now)
+            .err()
+    };
+
+    // the block authority can seal a block at any time.
+    assert_eq!(check("Alice", 0, parent_timestamp), None);
+
+    // each backup authority waits for one more failover delay than the previous one.
+    assert_eq!(
+        check("Charlie", 1, parent_timestamp + delay - 1),
+        Some(SealError::EarlyFailover(1)),
+    );
+    assert_eq!(check("Charlie", 1, parent_timestamp + delay), None);
+    assert_eq!(
+        check("Dave", 2, parent_timestamp + delay),
+        Some(SealError::EarlyFailover(2)),
+    );
+    assert_eq!(check("Dave", 2, parent_timestamp + 2 * delay), None);
+
+    // backups can't claim another authority's index or skip their delay by timestamping the
+    // block in the future.
+    assert_eq!(
+        check("Charlie", 0, parent_timestamp),
+        Some(SealError::BadSignature),
+    );
+    let future = now + MAX_TIMESTAMP_DRIFT.as_millis() as u64 + 1;
+    assert_eq!(
+        check("Charlie", 1, future),
+        Some(SealError::FutureTimestamp(future)),
+    );
+    assert_eq!(
+        check("Eve", 3, parent_timestamp + 3 * delay),
+        Some(SealError::UnknownAuthority(3)),
+    );
+}
+
+#[test]
+fn backup_authorities_cannot_timestamp_blocks_past_failover_delay() {
+    let mut config = singleton_config();
+    config.backup_authorities = vec![authority_pair("Charlie").public().into()];
+
+    let verifier = header_verifier(&config);
+    let delay = failover_delay_millis(config.failover_delay, 1);
+    let parent_timestamp = 1_000;
+
+    let header = Header::new(
+        2,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+
+    let check = |timestamp: u64, now: u64| {
+        let mut header = header.clone();
+        seal_header_as(&mut header, &authority_pair("Charlie"), 1, timestamp, None);
+        verifier
+            .check_header(&mut header, parent_timestamp, now)
+            .err()
+    };
+
+    // right after the parent, the earliest timestamp the backup may use is too far ahead.
+    assert_eq!(
+        check(parent_timestamp + delay, parent_timestamp),
+        Some(SealError::FutureTimestamp(parent_timestamp + delay)),
+    );
+    assert_eq!(
+        check(parent_timestamp + delay - 1, parent_timestamp),
+        Some(SealError::EarlyFailover(1)),
+    );
+    assert_eq!(
+        check(parent_timestamp + delay, parent_timestamp + 1),
+        Some(SealError::FutureTimestamp(parent_timestamp + delay)),
+    );
+
+    // once the failover delay has elapsed, the backup may seal.
+    assert_eq!(
+        check(parent_timestamp + delay, parent_timestamp + delay),
+        None
+    );
+}
+
+#[test]
+fn vrf_leaders_are_checked_against_slot_and_epoch_randomness() {
+    let mut config = singleton_config();
+    config.backup_authorities = vec![authority_pair("Charlie").public().into()];
+    config.leader_election = LeaderElection::Vrf;
+
+    let verifier = header_verifier(&config);
+    let key = authority_pair("Charlie");
+    let randomness = [7; 32];
+    let slot_duration = BLOCK_TIME.as_millis() as u64;
+
+    let sign = |slot: u64| {
+        key.vrf_sign(vrf_transcript(&randomness, slot))
+            .expect("sr25519 keys support VRF; qed")
+    };
+    let pre_digest = |slot: u64, vrf: Option<VrfSignature>| PreDigest {
+        authority_index: 1,
+        timestamp: slot * slot_duration,
+        vrf,
+    };
+
+    // VRF outputs are deterministic, so these are the same on every run.
+    let threshold = vrf_threshold(2);
+    let leader_slot = (1..).find(|slot| sign(*slot).1 < threshold).unwrap();
+    let other_slot = (1..).find(|slot| sign(*slot).1 >= threshold).unwrap();
+
+    let elected = pre_digest(leader_slot, Some(sign(leader_slot).0));
+    assert_eq!(verifier.check_vrf(&elected, 0, &randomness), Ok(()));
+
+    // the slot must be later than the parent's.
+    let parent_timestamp = leader_slot * slot_duration;
+    assert_eq!(
+        verifier.check_vrf(&elected, parent_timestamp, &randomness),
+        Err(SealError::StaleSlot(leader_slot)),
+    );
+
+    // the VRF is bound to the slot and the epoch randomness.
+    assert_eq!(
+        verifier.check_vrf(&pre_digest(leader_slot, None), 0, &randomness),
+        Err(SealError::MissingVrf),
+    );
+    assert_eq!(
+        verifier.check_vrf(
+            &pre_digest(other_slot, Some(sign(leader_slot).0)),
+            0,
+            &randomness
+        ),
+        Err(SealError::BadVrf),
+    );
+    assert_eq!(
+        verifier.check_vrf(&elected, 0, &[8; 32]),
+        Err(SealError::BadVrf),
+    );
+
+    // and the output must be below the threshold.
+    assert_eq!(
+        verifier.check_vrf(
+            &pre_digest(other_slot, Some(sign(other_slot).0)),
+            0,
+            &randomness
+        ),
+        Err(SealError::NotSlotLeader(1)),
+    );
+
+    // the failover delay doesn't apply to elected authorities, but VRFs are rejected without VRF
+    // leader election.
+    let header = Header::new(
+        1,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+    let timestamp = leader_slot * slot_duration;
+
+    let mut sealed = header.clone();
+    seal_header_as(&mut sealed, &key, 1, timestamp, elected.vrf.clone());
+    assert!(verifier
+        .check_header(&mut sealed.clone(), 0, timestamp)
+        .is_ok());
+
+    config.leader_election = LeaderElection::Fixed;
+    assert_eq!(
+        header_verifier(&config)
+            .check_header(&mut sealed, 0, timestamp)
+            .err(),
+        Some(SealError::UnexpectedVrf),
+    );
+}
+
+#[test]
+fn vrf_leaders_cannot_timestamp_blocks_a_block_time_ahead() {
+    let mut config = singleton_config();
+    config.leader_election = LeaderElection::Vrf;
+    // shorter than the maximum drift, which then doesn't matter.
+    config.block_time = MAX_TIMESTAMP_DRIFT / 2;
+
+    let verifier = header_verifier(&config);
+    let key = authority_pair("Alice");
+    let randomness = [7; 32];
+    let slot_duration = config.block_time.as_millis() as u64;
+
+    // `check_header` leaves the VRF to `check_vrf`, only the slot's timestamp matters here.
+    let slot = 5;
+    let timestamp = slot * slot_duration;
+    let vrf = key
+        .vrf_sign(vrf_transcript(&randomness, slot))
+        .map(|(signature, _)| signature);
+
+    let header = Header::new(
+        1,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+    let check = |now: u64| {
+        let mut header = header.clone();
+        seal_header_as(&mut header, &key, 0, timestamp, vrf.clone());
+        verifier.check_header(&mut header, 0, now).err()
+    };
+
+    assert_eq!(
+        check(timestamp - slot_duration),
+        Some(SealError::FutureTimestamp(timestamp)),
+    );
+    assert_eq!(check(timestamp - slot_duration + 1), None);
+}
+
+#[test]
+fn pow_seals_are_checked_against_difficulty_and_slot() {
+    const DIFFICULTY: u128 = 16;
+
+    let mut config = singleton_config();
+    config.pow_difficulty = Some(DIFFICULTY);
+
+    let verifier = header_verifier(&config);
+    let slot_duration = BLOCK_TIME.as_millis() as u64;
+    let timestamp = 5 * slot_duration;
+
+    let mut header = Header::new(
+        1,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+    header.digest_mut().push(pow_pre_runtime_digest(timestamp));
+
+    let pre_hash = header.hash();
+    let works = |nonce: &u64| meets_difficulty(&pow_hash(pre_hash.as_ref(), *nonce), DIFFICULTY);
+    let sealed_with = |nonce: u64| {
+        let mut header = header.clone();
+        header.digest_mut().push(PowSeal { nonce }.into());
+        header
+    };
+
+    // hashes are deterministic, so these are the same on every run.
+    let mined = sealed_with((0..).find(works).unwrap());
+    let unmined = sealed_with((0..).find(|nonce| !works(nonce)).unwrap());
+
+    let (_, pre_digest) = verifier
+        .check_pow_header(&mut mined.clone(), 0, timestamp)
+        .unwrap();
+    assert_eq!(pre_digest.timestamp, timestamp);
+
+    // the hash must be below the difficulty target.
+    assert_eq!(
+        verifier
+            .check_pow_header(&mut unmined.clone(), 0, timestamp)
+            .err(),
+        Some(SealError::InsufficientWork),
+    );
+
+    // the slot must be later than the parent's.
+    assert_eq!(
+        verifier
+            .check_pow_header(&mut mined.clone(), timestamp, timestamp)
+            .err(),
+        Some(SealError::StaleSlot(5)),
+    );
+
+    // blocks can't be identified both as mined and as sealed by an authority.
+    let mut mixed = header.clone();
+    mixed
+        .digest_mut()
+        .push(pre_runtime_digest(BLOCK_AUTHORITY_INDEX, timestamp, None));
+    mixed.digest_mut().push(PowSeal { nonce: 0 }.into());
+    assert_eq!(
+        verifier.check_pow_header(&mut mixed, 0, timestamp).err(),
+        Some(SealError::UnexpectedEngine(SINGLETON_ENGINE_ID)),
+    );
+
+    // proof of work seals are rejected unless the runtime allows them.
+    config.pow_difficulty = None;
+    assert_eq!(
+        header_verifier(&config)
+            .check_pow_header(&mut mined.clone(), 0, timestamp)
+            .err(),
+        Some(SealError::WrongEngine(SINGLETON_POW_ENGINE_ID)),
+    );
+}
+
+#[test]
+fn rejects_invalid_finality_messages() {
+    let finality_authority = singleton_config().finality_authority;
+    let block_hash = Hash::repeat_byte(1);
+
+    let check = |data: &[u8]| {
+        check_finality_message::<Hash, AuthorityPair, _>(data, |_| Ok(finality_authority.clone()))
+            .map(|_| ())
+    };
+
+    let message = |key: &AuthorityPair| {
+        SingletonFinalityMessage {
+            block_hash,
+            proof: SingletonFinalityJustification(key.sign(block_hash.as_ref())),
+        }
+        .encode()
+    };
+
+    assert!(matches!(check(&[1, 2, 3]), Err(Error::GossipDecode(_))));
+    assert!(matches!(
+        check(&message(&authority_pair("Eve"))),
+        Err(Error::BadFinalityProof)
+    ));
+    assert!(check(&message(&authority_pair("Bob"))).is_ok());
+
+    // errors that don't originate from the import pipeline are boxed when converted.
+    let err = check(&[1, 2, 3]).unwrap_err();
+    assert!(matches!(
+        ConsensusError::from(err),
+        ConsensusError::Other(_)
+    ));
+}
@@ -1,12 +1,35 @@
+use consensus_primitives::LeaderElection;
+use hex_literal::hex;
 use node_template_runtime::{
-    AccountId, BalancesConfig, GenesisConfig, Signature, SudoConfig, SystemConfig, WASM_BINARY,
+    opaque::SessionKeys,
+    singleton_crypto::{AuthorityId as SingletonId, FinalityId as SingletonFinalityId},
+    AccountId, AuraConfig, Balance, BalancesConfig, GenesisConfig, GrandpaConfig, Perbill,
+    RewardsConfig, SessionConfig, Signature, SingletonConfig, SudoConfig, SystemConfig,
+    MILLISECS_PER_BLOCK, WASM_BINARY,
 };
-use sc_service::ChainType;
-use sp_core::{sr25519, Pair, Public};
+use sc_service::{config::MultiaddrWithPeerId, ChainType, Properties};
+use sc_telemetry::TelemetryEndpoints;
+use serde::Deserialize;
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_core::{crypto::UncheckedInto, sr25519, Pair, Public};
+use sp_finality_grandpa::AuthorityId as GrandpaId;
 use sp_runtime::traits::{IdentifyAccount, Verify};
 
 // The URL for the telemetry server.
-// const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
+const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
+
+/// Bootnodes of the staging network. These are placeholders, which must be replaced with the
+/// operators' bootnodes before launching it.
+const STAGING_BOOTNODES: &[&str] = &[
+    "/dns4/boot-0.staging.singleton.barcamp.network/tcp/30333/p2p/12D3KooWGDas4zu5zpnq5cBqcXdA5szNEZpAgC4VuRo8vt7cmXHh",
+    "/dns4/boot-1.staging.singleton.barcamp.network/tcp/30333/p2p/12D3KooWAtUKRAMsWBLBUH6YWUw3KfMhtxPqj8bo4DuLov2JuXHF",
+];
+
+/// Amount minted for each authored block.
+const BLOCK_REWARD: Balance = 1 << 40;
+
+/// Share of each block reward paid to the finality authority.
+const FINALITY_REWARD_SHARE: Perbill = Perbill::from_percent(20);
 
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
 pub type ChainSpec = sc_service::GenericChainSpec<GenesisConfig>;
@@ -28,6 +51,27 @@ where
     AccountPublic::from(get_from_seed::<TPublic>(seed)).into_account()
 }
 
+/// Session keys of a validator: its singleton block authority and finality keys, and its Aura and
+/// GRANDPA keys.
+type AuthorityKeys = (
+    AccountId,
+    SingletonId,
+    SingletonFinalityId,
+    AuraId,
+    GrandpaId,
+);
+
+/// Generate a validator account with its session keys.
+pub fn authority_keys_from_seed(seed: &str, finality_seed: &str) -> AuthorityKeys {
+    (
+        get_account_id_from_seed::<sr25519::Public>(seed),
+        get_from_seed::<SingletonId>(seed),
+        get_from_seed::<SingletonFinalityId>(finality_seed),
+        get_from_seed::<AuraId>(seed),
+        get_from_seed::<GrandpaId>(seed),
+    )
+}
+
 pub fn development_config() -> Result<ChainSpec, String> {
     let wasm_binary = WASM_BINARY.ok_or("Development wasm binary not available".to_string())?;
 
@@ -40,6 +84,8 @@ pub fn development_config() -> Result<ChainSpec, String> {
         move || {
             testnet_genesis(
                 wasm_binary,
+                // Initial validators, Alice authors blocks which are finalized with Bob's key
+                vec![authority_keys_from_seed("Alice", "Bob")],
                 // Sudo account
                 get_account_id_from_seed::<sr25519::Public>("Alice"),
                 // Pre-funded accounts
@@ -49,6 +95,9 @@ pub fn development_config() -> Result<ChainSpec, String> {
                     get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
                     get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
                 ],
+                LeaderElection::Fixed,
+                MILLISECS_PER_BLOCK,
+                None,
                 true,
             )
         },
@@ -77,6 +126,12 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
         move || {
             testnet_genesis(
                 wasm_binary,
+                // Initial validators, Alice authors blocks which are finalized with Bob's key and
+                // Charlie is the backup author
+                vec![
+                    authority_keys_from_seed("Alice", "Bob"),
+                    authority_keys_from_seed("Charlie", "Charlie"),
+                ],
                 // Sudo account
                 get_account_id_from_seed::<sr25519::Public>("Alice"),
                 // Pre-funded accounts
@@ -94,6 +149,9 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
                     get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
                     get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
                 ],
+                LeaderElection::Fixed,
+                MILLISECS_PER_BLOCK,
+                None,
                 true,
             )
         },
@@ -110,11 +168,240 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
     ))
 }
 
+/// Chain spec of the staging network, where each validator has a separate key for every role. The
+/// session keys and accounts are placeholders, which must be replaced with those of the staging
+/// validators and operators before launching it, as their secrets aren't public.
+pub fn staging_config() -> Result<ChainSpec, String> {
+    let wasm_binary = WASM_BINARY.ok_or("Staging wasm binary not available".to_string())?;
+
+    let boot_nodes = parse_boot_nodes(STAGING_BOOTNODES)?;
+
+    let telemetry = TelemetryEndpoints::new(vec![(STAGING_TELEMETRY_URL.to_string(), 0)])
+        .map_err(|err| format!("Invalid telemetry URL: {}", err))?;
+
+    let mut properties = Properties::new();
+    properties.insert("tokenSymbol".into(), "SNGL".into());
+    properties.insert("tokenDecimals".into(), 12.into());
+
+    Ok(ChainSpec::from_genesis(
+        // Name
+        "Staging Testnet",
+        // ID
+        "staging_testnet",
+        ChainType::Live,
+        move || {
+            testnet_genesis(
+                wasm_binary,
+                // Initial validators, the first one authors blocks which are finalized with its
+                // separate finality key and the second one is the backup author
+                vec![
+                    (
+                        hex!["fcf9698e802fcbcbaec31820edc46834b5edcc5daced2ebaa6d6e590fdf9d327"]
+                            .into(),
+                        hex!["1284d8540a4f1036c8bae9c859cee57dbc7460433427712a82a492869fefb719"]
+                            .unchecked_into(),
+                        hex!["10f5d5c5dddf59ab9c53f34372a62ed21f1b734eeab3ed4758dacb085f9fbb6d"]
+                            .unchecked_into(),
+                        hex!["1eb35136467a91e3dd46e88593f92f01e2a38cc7adf41b367e9b95fa3947a564"]
+                            .unchecked_into(),
+                        hex!["242a8859ef723905c488301f71d790ae46b2cf0e21834eb1e5df7186fc370014"]
+                            .unchecked_into(),
+                    ),
+                    (
+                        hex!["ac26a2f48a66dde553b5d7acde41001f9c5b99df52dead3a9e840ac3b5b6385c"]
+                            .into(),
+                        hex!["2e9ffd382eb9fd788bed4839446ca2c2199485ec4097eb0a96cb0a688d8a7829"]
+                            .unchecked_into(),
+                        hex!["7a0226235e4503a71fcf18fb825235d7899b117d5bc538fab2e9b11d5cf8dd74"]
+                            .unchecked_into(),
+                        hex!["0e5c60ec2c7af6a28b0f5555474ef580b04713d71a2f937ccc50d47001114a38"]
+                            .unchecked_into(),
+                        hex!["5e46918423d3e2a5aeb5b4bb98865b506ab5f73465797587147f718e62547516"]
+                            .unchecked_into(),
+                    ),
+                ],
+                // Sudo account
+                hex!["62292ae1a524560d8d1705845fda18ac27415efe614ae02007b0788907bb4f00"].into(),
+                // Pre-funded accounts
+                vec![
+                    hex!["fcf9698e802fcbcbaec31820edc46834b5edcc5daced2ebaa6d6e590fdf9d327"].into(),
+                    hex!["ac26a2f48a66dde553b5d7acde41001f9c5b99df52dead3a9e840ac3b5b6385c"].into(),
+                    hex!["788e81e45cf8cb420549494ae590223cb01ae5c95f0b066cf5e11deccb73600c"].into(),
+                    hex!["62292ae1a524560d8d1705845fda18ac27415efe614ae02007b0788907bb4f00"].into(),
+                ],
+                LeaderElection::Fixed,
+                MILLISECS_PER_BLOCK,
+                None,
+                false,
+            )
+        },
+        // Bootnodes
+        boot_nodes,
+        // Telemetry
+        Some(telemetry),
+        // Protocol ID
+        Some("singleton-staging"),
+        // Properties
+        Some(properties),
+        // Extensions
+        None,
+    ))
+}
+
+/// Session keys of a validator of a singleton network, as given to `build-singleton-spec`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SingletonSpecAuthority {
+    pub account: AccountId,
+    pub singleton: SingletonId,
+    /// Only given for the first authority, whose finality key is the finality authority. The
+    /// others use their singleton key until they rotate their session keys.
+    #[serde(default)]
+    pub singleton_finality: Option<SingletonFinalityId>,
+    pub aura: AuraId,
+    pub grandpa: GrandpaId,
+}
+
+/// Parameters of a singleton network, as given to `build-singleton-spec`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SingletonSpecParams {
+    pub name: String,
+    pub id: String,
+    /// Initial validators. The first one authors blocks which are finalized with its finality
+    /// key, and the others are backup authors.
+    pub authorities: Vec<SingletonSpecAuthority>,
+    pub sudo: AccountId,
+    pub endowed_accounts: Vec<AccountId>,
+    #[serde(default)]
+    pub leader_election: LeaderElection,
+    /// Time between blocks in milliseconds.
+    #[serde(default = "default_block_time")]
+    pub block_time: u64,
+    #[serde(default)]
+    pub pow_difficulty: Option<u128>,
+    /// Multiaddresses of the bootnodes, ending with their peer ID.
+    #[serde(default)]
+    pub boot_nodes: Vec<String>,
+    #[serde(default)]
+    pub protocol_id: Option<String>,
+    /// Chain properties such as `tokenSymbol` and `tokenDecimals`.
+    #[serde(default)]
+    pub properties: Option<Properties>,
+}
+
+fn default_block_time() -> u64 {
+    MILLISECS_PER_BLOCK
+}
+
+/// Builds the chain spec of a live singleton network from the given parameters.
+pub fn singleton_config(params: SingletonSpecParams) -> Result<ChainSpec, String> {
+    let wasm_binary = WASM_BINARY.ok_or("Wasm binary not available".to_string())?;
+
+    if params.authorities.is_empty() {
+        return Err("At least one authority is required".into());
+    }
+
+    if params.block_time == 0 {
+        return Err("The block time must be positive".into());
+    }
+
+    if params.authorities[0].singleton_finality.is_none() {
+        return Err("The first authority must have a finality key".into());
+    }
+
+    // blocks are finalized by the single signature of the finality authority.
+    if params.authorities[1..]
+        .iter()
+        .any(|authority| authority.singleton_finality.is_some())
+    {
+        return Err("Only the first authority may have a finality key".into());
+    }
+
+    let SingletonSpecParams {
+        name,
+        id,
+        authorities,
+        sudo,
+        endowed_accounts,
+        leader_election,
+        block_time,
+        pow_difficulty,
+        boot_nodes,
+        protocol_id,
+        properties,
+    } = params;
+
+    let boot_nodes = parse_boot_nodes(&boot_nodes)?;
+
+    let initial_authorities: Vec<AuthorityKeys> = authorities
+        .into_iter()
+        .map(|authority| {
+            let singleton_finality = authority
+                .singleton_finality
+                .unwrap_or_else(|| sr25519::Public::from(authority.singleton.clone()).into());
+
+            (
+                authority.account,
+                authority.singleton,
+                singleton_finality,
+                authority.aura,
+                authority.grandpa,
+            )
+        })
+        .collect();
+
+    Ok(ChainSpec::from_genesis(
+        &name,
+        &id,
+        ChainType::Live,
+        move || {
+            testnet_genesis(
+                wasm_binary,
+                initial_authorities.clone(),
+                sudo.clone(),
+                endowed_accounts.clone(),
+                leader_election,
+                block_time,
+                pow_difficulty,
+                false,
+            )
+        },
+        // Bootnodes
+        boot_nodes,
+        // Telemetry
+        None,
+        // Protocol ID
+        protocol_id.as_deref(),
+        // Properties
+        properties,
+        // Extensions
+        None,
+    ))
+}
+
+/// Parses the multiaddresses of bootnodes, which must end with their peer ID.
+fn parse_boot_nodes<S: AsRef<str>>(addrs: &[S]) -> Result<Vec<MultiaddrWithPeerId>, String> {
+    addrs
+        .iter()
+        .map(|addr| {
+            let addr = addr.as_ref();
+            addr.parse()
+                .map_err(|err| format!("Invalid bootnode {}: {}", addr, err))
+        })
+        .collect()
+}
+
 /// Configure initial storage state for FRAME modules.
+#[allow(clippy::too_many_arguments)]
 fn testnet_genesis(
     wasm_binary: &[u8],
+    initial_authorities: Vec<AuthorityKeys>,
     root_key: AccountId,
     endowed_accounts: Vec<AccountId>,
+    leader_election: LeaderElection,
+    block_time: u64,
+    pow_difficulty: Option<u128>,
     _enable_println: bool,
 ) -> GenesisConfig {
     GenesisConfig {
@@ -135,5 +422,135 @@ fn testnet_genesis(
             // Assign network admin rights.
             key: root_key,
         }),
+        session: Some(SessionConfig {
+            keys: initial_authorities
+                .iter()
+                .map(|(account, singleton, singleton_finality, aura, grandpa)| {
+                    (
+                        account.clone(),
+                        account.clone(),
+                        SessionKeys {
+                            singleton: singleton.clone(),
+                            singleton_finality: singleton_finality.clone(),
+                            aura: aura.clone(),
+                            grandpa: grandpa.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        }),
+        // the singleton authorities are set from the session keys.
+        singleton: Some(SingletonConfig {
+            authorities: vec![],
+            finality_authority: None,
+            leader_election,
+            block_time,
+            pow_difficulty,
+        }),
+        // as are the Aura and GRANDPA authorities.
+        aura: Some(AuraConfig {
+            authorities: vec![],
+        }),
+        grandpa: Some(GrandpaConfig {
+            authorities: vec![],
+        }),
+        rewards: Some(RewardsConfig {
+            block_reward: BLOCK_REWARD,
+            finality_reward_share: FINALITY_REWARD_SHARE,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: &str = r#"{
+        "name": "Singleton Testnet",
+        "id": "singleton_testnet",
+        "authorities": [
+            {
+                "account": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+                "singleton": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+                "singletonFinality": "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty",
+                "aura": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+                "grandpa": "5FA9nQDVg267DEd8m1ZypXLBnvN7SFxYwV7ndqSYGiN9TTpu"
+            }
+        ],
+        "sudo": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+        "endowedAccounts": ["5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"],
+        "leaderElection": "Vrf",
+        "blockTime": 6000,
+        "powDifficulty": 16,
+        "bootNodes": [
+            "/ip4/127.0.0.1/tcp/30333/p2p/12D3KooWEyoppNCUx8Yx66oV9fJnriXwCcXwDDUA2kj6vnc6iDEp"
+        ],
+        "protocolId": "singleton-test",
+        "properties": {"tokenSymbol": "SNGL", "tokenDecimals": 12}
+    }"#;
+
+    fn build(params: &str) -> Result<serde_json::Value, String> {
+        let params: SingletonSpecParams = serde_json::from_str(params).unwrap();
+        let json = singleton_config(params)?.as_json(false)?;
+        Ok(serde_json::from_str(&json).unwrap())
+    }
+
+    #[test]
+    fn builds_singleton_spec_from_params() {
+        let spec = build(PARAMS).unwrap();
+
+        assert_eq!(spec["name"], "Singleton Testnet");
+        assert_eq!(spec["chainType"], "Live");
+        assert_eq!(spec["bootNodes"].as_array().map(Vec::len), Some(1));
+        assert_eq!(spec["protocolId"], "singleton-test");
+        assert_eq!(spec["properties"]["tokenSymbol"], "SNGL");
+
+        let genesis = &spec["genesis"]["runtime"];
+        assert_eq!(genesis["singleton"]["leaderElection"], "Vrf");
+        assert_eq!(genesis["singleton"]["blockTime"], 6000);
+        assert_eq!(genesis["singleton"]["powDifficulty"], 16);
+        // the singleton authorities are set from the session keys of the validators.
+        assert_eq!(genesis["session"]["keys"].as_array().map(Vec::len), Some(1));
+        // whose first validator's account is paid the finality reward share.
+        assert_eq!(
+            genesis["session"]["keys"][0][0],
+            "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_singleton_params() {
+        let with = |field: &str, value: &str| {
+            let mut params: serde_json::Value = serde_json::from_str(PARAMS).unwrap();
+            params[field] = serde_json::from_str(value).unwrap();
+            build(&params.to_string())
+        };
+
+        assert!(with("blockTime", "0").is_err());
+        assert!(with("bootNodes", r#"["/ip4/127.0.0.1/tcp/30333"]"#).is_err());
+        assert!(with("authorities", "[]").is_err());
+    }
+
+    #[test]
+    fn only_first_authority_has_finality_key() {
+        // blocks are finalized by the single finality key of the first authority.
+        let build_with_authorities = |finality_keys: &[Option<&str>]| {
+            let mut params: serde_json::Value = serde_json::from_str(PARAMS).unwrap();
+            let authority = params["authorities"][0].clone();
+            params["authorities"] = finality_keys
+                .iter()
+                .map(|finality_key| {
+                    let mut authority = authority.clone();
+                    authority["singletonFinality"] = serde_json::json!(finality_key);
+                    authority
+                })
+                .collect();
+            build(&params.to_string())
+        };
+        let finality_key = Some("5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty");
+
+        assert!(build_with_authorities(&[finality_key, None]).is_ok());
+        assert!(build_with_authorities(&[finality_key, finality_key]).is_err());
+        assert!(build_with_authorities(&[None, None]).is_err());
     }
 }
@@ -1,4 +1,7 @@
-use sc_cli::Subcommand;
+use crate::service::{Authoring, Finality};
+use node_template_runtime::BlockNumber;
+use sc_cli::{CliConfiguration, PruningParams, SharedParams};
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[allow(missing_docs)]
@@ -8,11 +11,227 @@ pub struct RunCmd {
     #[structopt(flatten)]
     pub base: sc_cli::RunCmd,
 
+    /// Block production engine, `singleton` or `aura`. Overrides the `authoring` property of the
+    /// chain spec, which defaults to `singleton`.
+    #[structopt(
+        long = "authoring",
+        value_name = "ENGINE",
+        possible_values = &["singleton", "aura"]
+    )]
+    pub authoring: Option<Authoring>,
+
+    /// Finality gadget to run, `singleton` or `grandpa`. Overrides the `finality` property of the
+    /// chain spec, which defaults to `singleton`.
+    #[structopt(
+        long = "finality",
+        value_name = "GADGET",
+        possible_values = &["singleton", "grandpa"]
+    )]
+    pub finality: Option<Finality>,
+
     #[structopt(long = "finality-gadget")]
     pub finality_gadget: bool,
 
-    #[structopt(long = "finality-gadget-validator")]
-    pub finality_gadget_validator: bool,
+    /// Author blocks with the singleton authority key with the given public key (SS58), which
+    /// must be in the keystore and be the on-chain block authority.
+    #[structopt(long = "singleton-author", value_name = "PUBLIC")]
+    pub singleton_author: Option<String>,
+
+    /// Mine blocks sealed with proof of work instead of an authority signature. The runtime
+    /// must set a proof of work difficulty.
+    #[structopt(long = "singleton-pow", conflicts_with = "singleton-author")]
+    pub singleton_pow: bool,
+
+    /// Sign finality proofs with the singleton finality key with the given public key (SS58),
+    /// which must be in the keystore and be the on-chain finality authority. Implies
+    /// `--finality-gadget`.
+    #[structopt(long = "singleton-finality-key", value_name = "PUBLIC")]
+    pub singleton_finality_key: Option<String>,
+
+    /// Number of blocks below the best block that the finality validator waits for before
+    /// finalizing a block.
+    #[structopt(
+        long = "finality-confirmation-depth",
+        value_name = "BLOCKS",
+        default_value = "0"
+    )]
+    pub finality_confirmation_depth: u32,
+
+    /// Pause block authoring while the best block is more than this many blocks ahead of the
+    /// last finalized block.
+    #[structopt(long = "max-unfinalized-depth", value_name = "BLOCKS")]
+    pub max_unfinalized_depth: Option<u32>,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, StructOpt)]
+pub enum Subcommand {
+    #[structopt(flatten)]
+    Base(sc_cli::Subcommand),
+
+    /// Export what the local singleton keys have signed, to protect them from double-signing
+    /// when moving them to another node.
+    ExportSigningHistory(SigningHistoryCmd),
+
+    /// Import the signing history exported by another node, before using its singleton keys.
+    ImportSigningHistory(SigningHistoryCmd),
+
+    /// Export the singleton finality justifications of a range of blocks.
+    ExportJustifications(ExportJustificationsCmd),
+
+    /// Verify exported singleton finality justifications and finalize the blocks they justify.
+    ImportJustifications(ImportJustificationsCmd),
+
+    /// Verify the seal and justification of every block of the local chain.
+    VerifyChain(VerifyChainCmd),
+
+    /// Decode and verify the singleton seal, digests and justification of a block.
+    InspectBlock(InspectBlockCmd),
+
+    /// Build a raw chain spec for a singleton network from a JSON file of parameters.
+    BuildSingletonSpec(BuildSingletonSpecCmd),
+}
+
+/// The `export-signing-history` and `import-signing-history` commands.
+#[derive(Debug, StructOpt)]
+pub struct SigningHistoryCmd {
+    /// SCALE encoded signing history file.
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub shared_params: SharedParams,
+
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub pruning_params: PruningParams,
+}
+
+impl CliConfiguration for SigningHistoryCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+
+    fn pruning_params(&self) -> Option<&PruningParams> {
+        Some(&self.pruning_params)
+    }
+}
+
+/// The `export-justifications` command.
+#[derive(Debug, StructOpt)]
+pub struct ExportJustificationsCmd {
+    /// File to write the SCALE encoded justifications to.
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+
+    /// First block to export justifications from.
+    #[structopt(long = "from", value_name = "BLOCK", default_value = "1")]
+    pub from: BlockNumber,
+
+    /// Last block to export justifications from. Defaults to the last finalized block.
+    #[structopt(long = "to", value_name = "BLOCK")]
+    pub to: Option<BlockNumber>,
+
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub shared_params: SharedParams,
+
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub pruning_params: PruningParams,
+}
+
+impl CliConfiguration for ExportJustificationsCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+
+    fn pruning_params(&self) -> Option<&PruningParams> {
+        Some(&self.pruning_params)
+    }
+}
+
+/// The `import-justifications` command.
+#[derive(Debug, StructOpt)]
+pub struct ImportJustificationsCmd {
+    /// File with SCALE encoded justifications written by `export-justifications`.
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub shared_params: SharedParams,
+
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub pruning_params: PruningParams,
+}
+
+impl CliConfiguration for ImportJustificationsCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+
+    fn pruning_params(&self) -> Option<&PruningParams> {
+        Some(&self.pruning_params)
+    }
+}
+
+/// The `verify-chain` command.
+#[derive(Debug, StructOpt)]
+pub struct VerifyChainCmd {
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub shared_params: SharedParams,
+
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub pruning_params: PruningParams,
+}
+
+impl CliConfiguration for VerifyChainCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+
+    fn pruning_params(&self) -> Option<&PruningParams> {
+        Some(&self.pruning_params)
+    }
+}
+
+/// The `inspect-block` command.
+#[derive(Debug, StructOpt)]
+pub struct InspectBlockCmd {
+    /// Number or hash (0x prefixed) of the block.
+    #[structopt(value_name = "BLOCK")]
+    pub block: String,
+
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub shared_params: SharedParams,
+
+    #[allow(missing_docs)]
+    #[structopt(flatten)]
+    pub pruning_params: PruningParams,
+}
+
+impl CliConfiguration for InspectBlockCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+
+    fn pruning_params(&self) -> Option<&PruningParams> {
+        Some(&self.pruning_params)
+    }
+}
+
+/// The `build-singleton-spec` command.
+#[derive(Debug, StructOpt)]
+pub struct BuildSingletonSpecCmd {
+    /// JSON file with the parameters of the network, see `chain_spec::SingletonSpecParams`.
+    #[structopt(parse(from_os_str))]
+    pub params: PathBuf,
 }
 
 #[derive(Debug, StructOpt)]
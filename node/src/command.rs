@@ -16,11 +16,21 @@
 // limitations under the License.
 
 use crate::chain_spec;
-use crate::cli::Cli;
+use crate::cli::{
+    BuildSingletonSpecCmd, Cli, ExportJustificationsCmd, ImportJustificationsCmd, InspectBlockCmd,
+    SigningHistoryCmd, Subcommand, VerifyChainCmd,
+};
 use crate::service;
-use crate::service::new_full_params;
+use crate::service::{new_full_params, SingletonAuthorityPair};
+use codec::{Decode, Encode};
+use consensus::{InspectedJustification, InspectedSeal};
+use log::info;
+use node_template_runtime::{opaque::Block, Hash};
 use sc_cli::{ChainSpec, Role, RuntimeVersion, SubstrateCli};
-use sc_service::ServiceParams;
+use sc_service::{Configuration, ServiceParams};
+use sp_core::{hexdisplay::HexDisplay, Pair};
+use sp_runtime::generic::BlockId;
+use std::fs;
 
 impl SubstrateCli for Cli {
     fn impl_name() -> String {
@@ -50,6 +60,7 @@ impl SubstrateCli for Cli {
     fn load_spec(&self, id: &str) -> Result<Box<dyn sc_service::ChainSpec>, String> {
         Ok(match id {
             "dev" => Box::new(chain_spec::development_config()?),
+            "staging" => Box::new(chain_spec::staging_config()?),
             "" | "local" => Box::new(chain_spec::local_testnet_config()?),
             path => Box::new(chain_spec::ChainSpec::from_json_file(
                 std::path::PathBuf::from(path),
@@ -67,7 +78,7 @@ pub fn run() -> sc_cli::Result<()> {
     let cli = Cli::from_args();
 
     match &cli.subcommand {
-        Some(subcommand) => {
+        Some(Subcommand::Base(subcommand)) => {
             let runner = cli.create_runner(subcommand)?;
             runner.run_subcommand(subcommand, |config| {
                 let (
@@ -79,19 +90,285 @@ pub fn run() -> sc_cli::Result<()> {
                         ..
                     },
                     ..,
-                ) = new_full_params(config)?;
+                ) = new_full_params(config, None, None)?;
                 Ok((client, backend, import_queue, task_manager))
             })
         }
+        Some(Subcommand::ExportSigningHistory(cmd)) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.sync_run(|config| cmd.export(config))
+        }
+        Some(Subcommand::ImportSigningHistory(cmd)) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.sync_run(|config| cmd.import(config))
+        }
+        Some(Subcommand::ExportJustifications(cmd)) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.sync_run(|config| cmd.run(config))
+        }
+        Some(Subcommand::ImportJustifications(cmd)) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.sync_run(|config| cmd.run(config))
+        }
+        Some(Subcommand::VerifyChain(cmd)) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.sync_run(|config| cmd.run(config))
+        }
+        Some(Subcommand::InspectBlock(cmd)) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.sync_run(|config| cmd.run(config))
+        }
+        Some(Subcommand::BuildSingletonSpec(cmd)) => cmd.run(),
         None => {
             let runner = cli.create_runner(&cli.run.base)?;
+            let authoring = cli.run.authoring;
+            let finality = cli.run.finality;
             let finality_gadget = cli.run.finality_gadget;
-            let finality_gadget_validator = cli.run.finality_gadget_validator;
+            let singleton_author = cli.run.singleton_author.clone();
+            let singleton_pow = cli.run.singleton_pow;
+            let singleton_finality_key = cli.run.singleton_finality_key.clone();
+            let finality_confirmation_depth = cli.run.finality_confirmation_depth;
+            let max_unfinalized_depth = cli.run.max_unfinalized_depth;
 
             runner.run_node_until_exit(|config| match config.role {
-                Role::Light => service::new_light(config),
-                _ => service::new_full(config, finality_gadget, finality_gadget_validator),
+                Role::Light => service::new_light(config, authoring, finality),
+                _ => service::new_full(
+                    config,
+                    authoring,
+                    finality,
+                    finality_gadget,
+                    singleton_author,
+                    singleton_pow,
+                    singleton_finality_key,
+                    finality_confirmation_depth,
+                    max_unfinalized_depth,
+                ),
             })
         }
     }
 }
+
+impl SigningHistoryCmd {
+    /// Writes the signing history of all local singleton keys to the file.
+    fn export(&self, config: Configuration) -> sc_cli::Result<()> {
+        let client = service::new_full_client(&config)?;
+        let history = consensus::load_signing_history::<Hash, _>(&*client)
+            .map_err(|err| sc_cli::Error::Other(err.to_string()))?;
+
+        fs::write(&self.file, history.encode())?;
+        Ok(())
+    }
+
+    /// Merges the signing history in the file into the local one. Local records are only ever
+    /// raised, so importing an older history is harmless.
+    fn import(&self, config: Configuration) -> sc_cli::Result<()> {
+        let encoded = fs::read(&self.file)?;
+        let history = consensus::SigningHistoryDb::<Hash>::decode(&mut &encoded[..])
+            .map_err(|err| sc_cli::Error::Input(format!("Invalid signing history: {}", err)))?;
+
+        let client = service::new_full_client(&config)?;
+        consensus::import_signing_history(&*client, history)
+            .map_err(|err| sc_cli::Error::Other(err.to_string()))
+    }
+}
+
+impl ExportJustificationsCmd {
+    /// Writes the justifications of the requested blocks to the file.
+    fn run(&self, config: Configuration) -> sc_cli::Result<()> {
+        let client = service::new_full_client(&config)?;
+        let to = self
+            .to
+            .unwrap_or_else(|| client.chain_info().finalized_number);
+
+        let justifications = consensus::export_justifications::<Block, _, SingletonAuthorityPair>(
+            &*client, self.from, to,
+        )
+        .map_err(|err| sc_cli::Error::Other(err.to_string()))?;
+
+        fs::write(&self.file, justifications.encode())?;
+        info!(
+            "Exported {} justifications of blocks #{} to #{}",
+            justifications.len(),
+            self.from,
+            to,
+        );
+        Ok(())
+    }
+}
+
+impl ImportJustificationsCmd {
+    /// Verifies the justifications in the file and finalizes the blocks they justify.
+    fn run(&self, config: Configuration) -> sc_cli::Result<()> {
+        let encoded = fs::read(&self.file)?;
+        let justifications = Vec::<
+            consensus::ExportedJustification<Block, <SingletonAuthorityPair as Pair>::Signature>,
+        >::decode(&mut &encoded[..])
+        .map_err(|err| sc_cli::Error::Input(format!("Invalid justifications: {}", err)))?;
+
+        let client = service::new_full_client(&config)?;
+        let config_at = consensus::runtime_config_at(client.clone(), service::allowed_engines());
+        let imported = consensus::import_justifications::<Block, _, _, SingletonAuthorityPair>(
+            &*client,
+            &config_at,
+            justifications,
+        )
+        .map_err(|err| sc_cli::Error::Other(err.to_string()))?;
+
+        info!(
+            "Finalized {} blocks, skipped {} already finalized blocks",
+            imported.finalized, imported.skipped,
+        );
+        Ok(())
+    }
+}
+
+impl VerifyChainCmd {
+    /// Verifies the local chain, failing if any block is invalid.
+    fn run(&self, config: Configuration) -> sc_cli::Result<()> {
+        // the authorities are read from the state at the parent of every block, which is only
+        // kept for the last 256 blocks unless pruning is disabled.
+        if !config.pruning.is_archive() {
+            return Err(sc_cli::Error::Input(
+                "verify-chain needs the state of every block, run it with `--pruning archive` \
+                 on the database of an archive node"
+                    .into(),
+            ));
+        }
+
+        let client = service::new_full_client(&config)?;
+        let verification = consensus::verify_chain::<Block, _, SingletonAuthorityPair>(
+            &*client,
+            &consensus::runtime_config_at(client.clone(), service::allowed_engines()),
+            &consensus::runtime_epoch_randomness::<_, _, SingletonAuthorityPair>(client.clone()),
+        )
+        .map_err(|err| sc_cli::Error::Other(err.to_string()))?;
+
+        info!("Verified {} blocks", verification.blocks);
+        for (authority_index, blocks) in &verification.sealed {
+            info!("  {} sealed by authority #{}", blocks, authority_index);
+        }
+        info!("  {} sealed with proof of work", verification.mined);
+        info!(
+            "Verified {} justifications, skipped {} of other finality gadgets",
+            verification.justifications, verification.foreign_justifications,
+        );
+
+        match verification.first_invalid {
+            Some((number, hash, err)) => Err(sc_cli::Error::Other(format!(
+                "Block #{} ({:?}) is invalid: {}",
+                number, hash, err
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl InspectBlockCmd {
+    /// Parses the block number or hash.
+    fn block_id(&self) -> sc_cli::Result<BlockId<Block>> {
+        if self.block.starts_with("0x") {
+            self.block[2..]
+                .parse::<Hash>()
+                .map(BlockId::Hash)
+                .map_err(|err| sc_cli::Error::Input(format!("Invalid block hash: {:?}", err)))
+        } else {
+            self.block
+                .parse()
+                .map(BlockId::Number)
+                .map_err(|err| sc_cli::Error::Input(format!("Invalid block number: {}", err)))
+        }
+    }
+
+    /// Prints the singleton consensus details of the block.
+    fn run(&self, config: Configuration) -> sc_cli::Result<()> {
+        let block_id = self.block_id()?;
+        let client = service::new_full_client(&config)?;
+        let inspection = consensus::inspect_block::<Block, _, SingletonAuthorityPair>(
+            &*client,
+            block_id,
+            &consensus::runtime_config_at(client.clone(), service::allowed_engines()),
+            &consensus::runtime_epoch_randomness::<_, _, SingletonAuthorityPair>(client.clone()),
+        )
+        .map_err(|err| sc_cli::Error::Other(err.to_string()))?;
+
+        println!("Block #{}", inspection.number);
+        println!("  Pre-hash:  {:?}", inspection.pre_hash);
+        println!("  Post-hash: {:?}", inspection.post_hash);
+
+        match &inspection.seal {
+            Some(InspectedSeal::Authority(signature)) => println!("  Seal: {:?}", signature),
+            Some(InspectedSeal::Work(nonce)) => println!("  Seal: proof of work, nonce {}", nonce),
+            Some(InspectedSeal::Other(id, data)) => println!(
+                "  Seal: {} 0x{}",
+                String::from_utf8_lossy(id),
+                HexDisplay::from(data)
+            ),
+            None => println!("  Seal: none"),
+        }
+
+        if let Some(pre_digest) = &inspection.pre_digest {
+            println!(
+                "  Authority: #{} ({}), timestamp {}{}",
+                pre_digest.authority_index,
+                inspection
+                    .authority
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_else(|| "unknown".into()),
+                pre_digest.timestamp,
+                if pre_digest.vrf.is_some() {
+                    ", with VRF"
+                } else {
+                    ""
+                },
+            );
+        }
+
+        if let Some(pow_pre_digest) = &inspection.pow_pre_digest {
+            println!("  Mined at timestamp {}", pow_pre_digest.timestamp);
+        }
+
+        match &inspection.seal_check {
+            Some(Ok(())) => println!("  Seal verifies"),
+            Some(Err(err)) => println!("  Seal doesn't verify: {}", err),
+            None => {}
+        }
+
+        for (id, data) in &inspection.consensus_logs {
+            println!(
+                "  Consensus log: {} 0x{}",
+                String::from_utf8_lossy(id),
+                HexDisplay::from(data)
+            );
+        }
+
+        match &inspection.justification {
+            Some(InspectedJustification::Singleton(signature, valid)) => println!(
+                "  Justification: {:?} ({})",
+                signature,
+                if *valid { "valid" } else { "invalid" }
+            ),
+            Some(InspectedJustification::Other(data)) => {
+                println!("  Justification: 0x{}", HexDisplay::from(data))
+            }
+            None => println!("  Justification: none"),
+        }
+
+        Ok(())
+    }
+}
+
+impl BuildSingletonSpecCmd {
+    /// Builds the chain spec and prints it in raw form, which doesn't depend on the genesis
+    /// building code of the node.
+    fn run(&self) -> sc_cli::Result<()> {
+        let params: chain_spec::SingletonSpecParams =
+            serde_json::from_reader(fs::File::open(&self.params)?)
+                .map_err(|err| sc_cli::Error::Input(format!("Invalid parameters: {}", err)))?;
+
+        let chain_spec = chain_spec::singleton_config(params).map_err(sc_cli::Error::Input)?;
+        let json = chain_spec.as_json(true).map_err(sc_cli::Error::Other)?;
+        println!("{}", json);
+        Ok(())
+    }
+}
@@ -1,11 +1,28 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
+use log::warn;
 use node_template_runtime::{self, opaque::Block, RuntimeApi};
-use sc_client_api::RemoteBackend;
+use sc_client_api::{ExecutorProvider, RemoteBackend};
 use sc_executor::native_executor_instance;
 pub use sc_executor::NativeExecutor;
-use sc_service::{error::Error as ServiceError, Configuration, ServiceComponents, TaskManager};
+use sc_finality_grandpa::{
+    FinalityProofProvider as GrandpaFinalityProofProvider, SharedVoterState,
+    StorageAndProofProvider,
+};
+use sc_keystore::KeyStorePtr;
+use sc_service::{
+    error::Error as ServiceError, ChainType, Configuration, ServiceComponents, TaskManager,
+};
+use sp_api::TransactionFor;
+use sp_consensus::import_queue::{BoxBlockImport, BoxJustificationImport};
+use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
+use sp_core::crypto::{Public as _, Ss58Codec};
+use sp_inherents::InherentDataProviders;
+use sp_keyring::{Ed25519Keyring, Sr25519Keyring};
+use sp_runtime::generic::BlockId;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 // Our native executor instance.
 native_executor_instance!(
@@ -14,12 +31,295 @@ native_executor_instance!(
     node_template_runtime::native_version,
 );
 
-type FullClient = sc_service::TFullClient<Block, RuntimeApi, Executor>;
+pub type FullClient = sc_service::TFullClient<Block, RuntimeApi, Executor>;
 type FullBackend = sc_service::TFullBackend<Block>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
 
+/// Block import below the block production engine's: the client itself, or the GRANDPA block
+/// import wrapping it, which must see every block to track authority set changes.
+pub type FullBlockImport = BoxBlockImport<Block, TransactionFor<FullClient, Block>>;
+
+/// The key pair type used to sign singleton seals and finality justifications. This must match
+/// the signature scheme reported by the runtime's `SingletonApi`.
+pub type SingletonAuthorityPair = consensus_primitives::sr25519::AuthorityPair;
+
+/// The key pair type of the singleton finality key, which is stored in the keystore under its
+/// own key type so that it can be rotated with the session keys.
+type SingletonFinalityPair = consensus_primitives::sr25519::FinalityPair;
+
+type SingletonPublic = <SingletonAuthorityPair as sp_core::Pair>::Public;
+
+/// Chain spec property selecting the block production engine, `singleton` or `aura`.
+const AUTHORING_PROPERTY: &str = "authoring";
+
+/// Chain spec property selecting the finality gadget, `singleton` or `grandpa`.
+const FINALITY_PROPERTY: &str = "finality";
+
+/// Other consensus engines whose digest items singleton blocks may carry: the GRANDPA and Aura
+/// pallets log their authority changes at session boundaries, whichever engines are in use.
+pub fn allowed_engines() -> Vec<sp_runtime::ConsensusEngineId> {
+    vec![
+        sp_finality_grandpa::GRANDPA_ENGINE_ID,
+        sp_consensus_aura::AURA_ENGINE_ID,
+    ]
+}
+
+/// The block production engine.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Authoring {
+    /// Blocks are sealed by the singleton block authority, or by its backups.
+    Singleton,
+    /// Blocks are sealed by the Aura authorities in turn, one per slot.
+    Aura,
+}
+
+impl FromStr for Authoring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "singleton" => Ok(Authoring::Singleton),
+            "aura" => Ok(Authoring::Aura),
+            _ => Err(format!("Unknown block production engine: {}", s)),
+        }
+    }
+}
+
+/// The finality gadget.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Finality {
+    /// Blocks are finalized by the singleton finality authority.
+    Singleton,
+    /// Blocks are finalized by the GRANDPA authorities, tolerating faulty authorities.
+    Grandpa,
+}
+
+impl FromStr for Finality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "singleton" => Ok(Finality::Singleton),
+            "grandpa" => Ok(Finality::Grandpa),
+            _ => Err(format!("Unknown finality gadget: {}", s)),
+        }
+    }
+}
+
+/// Returns the option given on the command line if any, otherwise the one set by the given chain
+/// spec property, defaulting to `default`.
+fn select<T: FromStr<Err = String>>(
+    option: Option<T>,
+    config: &Configuration,
+    property: &str,
+    default: T,
+) -> Result<T, ServiceError> {
+    if let Some(option) = option {
+        return Ok(option);
+    }
+
+    match config.chain_spec.properties().get(property) {
+        Some(value) => value
+            .as_str()
+            .ok_or_else(|| format!("Invalid `{}` chain spec property", property))
+            .and_then(str::parse)
+            .map_err(ServiceError::Other),
+        None => Ok(default),
+    }
+}
+
+/// Reads the singleton authorities from the runtime at the given block.
+fn singleton_config<Client>(
+    client: &Client,
+    at: BlockId<Block>,
+) -> Result<consensus::SingletonConfig<SingletonAuthorityPair>, ServiceError>
+where
+    Client: sp_api::ProvideRuntimeApi<Block>,
+    Client::Api: consensus::SingletonApi<Block, SingletonPublic, Error = sp_blockchain::Error>,
+{
+    consensus::SingletonConfig::from_runtime(client, &at, allowed_engines())
+        .map_err(|err| ServiceError::Other(err.to_string()))
+}
+
+/// Block and finality authorities queued in the runtime to take over at the next session.
+struct QueuedAuthorities {
+    block_authorities: Vec<SingletonPublic>,
+    finality_authority: Option<SingletonPublic>,
+}
+
+/// Reads the queued singleton authorities from the runtime at the given block.
+fn queued_authorities<Client>(
+    client: &Client,
+    at: BlockId<Block>,
+) -> Result<QueuedAuthorities, ServiceError>
+where
+    Client: sp_api::ProvideRuntimeApi<Block>,
+    Client::Api: consensus::SingletonApi<Block, SingletonPublic, Error = sp_blockchain::Error>,
+{
+    use consensus::SingletonApi;
+
+    let runtime_api = client.runtime_api();
+    let block_authorities = runtime_api
+        .next_authorities(&at)
+        .map_err(|err| ServiceError::Other(err.to_string()))?;
+    let finality_authority = runtime_api
+        .next_finality_authority(&at)
+        .map_err(|err| ServiceError::Other(err.to_string()))?;
+
+    Ok(QueuedAuthorities {
+        block_authorities,
+        finality_authority,
+    })
+}
+
+/// Parses an SS58 encoded public key passed on the command line.
+fn parse_public<Public: Ss58Codec>(option: &str, public: &str) -> Result<Public, ServiceError> {
+    Public::from_string(public)
+        .map_err(|err| ServiceError::Other(format!("Invalid {} {}: {:?}", option, public, err)))
+}
+
+/// Loads the block authority key pair with the given public key from the keystore, checking that
+/// it is the block authority or one of the backup authorities in the runtime, now or from the next
+/// session on.
+fn load_author_key(
+    keystore: &KeyStorePtr,
+    singleton_config: &consensus::SingletonConfig<SingletonAuthorityPair>,
+    queued: &QueuedAuthorities,
+    public: &str,
+) -> Result<consensus::SingletonBlockAuthorityPair<SingletonAuthorityPair>, ServiceError> {
+    let public = parse_public("--singleton-author", public)?;
+
+    if let Err(err) = singleton_config.block_authority_index(&public) {
+        if !queued.block_authorities.contains(&public) {
+            return Err(ServiceError::Other(format!(
+                "{}, nor queued for the next session",
+                err
+            )));
+        }
+
+        warn!(
+            "{}, but is queued for the next session; not authoring blocks until then.",
+            err
+        );
+    }
+
+    let pair = keystore
+        .read()
+        .key_pair::<SingletonAuthorityPair>(&public)
+        .map_err(|err| ServiceError::Other(format!("Singleton author key {}: {}", public, err)))?;
+
+    Ok(pair.into())
+}
+
+/// Loads the finality key pair with the given public key from the keystore, checking that it is
+/// the finality authority in the runtime, now or from the next session on.
+fn load_finality_key(
+    keystore: &KeyStorePtr,
+    singleton_config: &consensus::SingletonConfig<SingletonAuthorityPair>,
+    queued: &QueuedAuthorities,
+    public: &str,
+) -> Result<consensus::SingletonFinalityAuthorityPair<SingletonAuthorityPair>, ServiceError> {
+    let public: <SingletonFinalityPair as sp_core::Pair>::Public =
+        parse_public("--singleton-finality-key", public)?;
+
+    // the consensus engine signs finality proofs with the block authority key pair type, so the
+    // finality key is converted through the underlying crypto pair.
+    let generic: sp_core::sr25519::Public = public.clone().into();
+    let authority: SingletonPublic = generic.into();
+    if let Err(err) = singleton_config.ensure_finality_authority(&authority) {
+        if queued.finality_authority.as_ref() != Some(&authority) {
+            return Err(ServiceError::Other(format!(
+                "{}, nor queued for the next session",
+                err
+            )));
+        }
+
+        warn!(
+            "{}, but is queued for the next session; not finalizing blocks until then.",
+            err
+        );
+    }
+
+    let pair = keystore
+        .read()
+        .key_pair::<SingletonFinalityPair>(&public)
+        .map_err(|err| {
+            ServiceError::Other(format!("Singleton finality key {}: {}", public, err))
+        })?;
+    let generic: sp_core::sr25519::Pair = pair.into();
+
+    Ok(SingletonAuthorityPair::from(generic).into())
+}
+
+/// Refuses to run a chain other than a development or local one whose singleton authorities are
+/// well-known development keys of `sp_keyring`, or with a development key in the keystore, as
+/// anyone can sign with them.
+fn ensure_no_dev_keys(
+    chain_spec: &dyn sc_service::ChainSpec,
+    dev_key_seed: Option<&str>,
+    singleton_config: &consensus::SingletonConfig<SingletonAuthorityPair>,
+) -> Result<(), ServiceError> {
+    match chain_spec.chain_type() {
+        ChainType::Development | ChainType::Local => return Ok(()),
+        ChainType::Live | ChainType::Custom(_) => {}
+    }
+
+    if let Some(seed) = dev_key_seed {
+        return Err(ServiceError::Other(format!(
+            "Refusing to use the development key {} on the {} chain",
+            seed,
+            chain_spec.name()
+        )));
+    }
+
+    let dev_keys: Vec<Vec<u8>> = Sr25519Keyring::iter()
+        .map(|keyring| keyring.public().to_raw_vec())
+        .chain(Ed25519Keyring::iter().map(|keyring| keyring.public().to_raw_vec()))
+        .collect();
+
+    let authorities = std::iter::once(&singleton_config.block_authority)
+        .chain(&singleton_config.backup_authorities)
+        .map(|authority| authority.as_ref())
+        .chain(std::iter::once(
+            singleton_config.finality_authority.as_ref(),
+        ));
+
+    for public in authorities {
+        if dev_keys.contains(&public.to_raw_vec()) {
+            return Err(ServiceError::Other(format!(
+                "Refusing to start the {} chain, whose singleton authority {} is a development key",
+                chain_spec.name(),
+                public
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Consensus components of a full node, built alongside its service params.
+pub struct FullConsensus {
+    /// Looks up the configuration in effect at any block, as the authorities rotate their keys.
+    pub config_at: consensus::ConfigAt<Block, SingletonAuthorityPair>,
+    pub authoring: Authoring,
+    pub select_chain: FullSelectChain,
+    /// Block import for the blocks authored by this node.
+    pub block_import: FullBlockImport,
+    pub inherent_data_providers: InherentDataProviders,
+    /// Set if GRANDPA is the finality gadget.
+    pub grandpa_link: Option<sc_finality_grandpa::LinkHalf<Block, FullClient, FullSelectChain>>,
+}
+
+/// Opens the local database of a full client, without starting any of the node's services.
+pub fn new_full_client(config: &Configuration) -> Result<Arc<FullClient>, ServiceError> {
+    let (client, _, _, _) = sc_service::new_full_parts::<Block, RuntimeApi, Executor>(config)?;
+    Ok(Arc::new(client))
+}
+
 pub fn new_full_params(
     config: Configuration,
+    authoring: Option<Authoring>,
+    finality: Option<Finality>,
 ) -> Result<
     (
         sc_service::ServiceParams<
@@ -30,15 +330,20 @@ pub fn new_full_params(
             (),
             FullBackend,
         >,
-        consensus::SingletonConfig,
-        FullSelectChain,
+        FullConsensus,
     ),
     ServiceError,
 > {
+    let authoring = select(authoring, &config, AUTHORING_PROPERTY, Authoring::Singleton)?;
+    let finality = select(finality, &config, FINALITY_PROPERTY, Finality::Singleton)?;
+
     let (client, backend, keystore, task_manager) =
         sc_service::new_full_parts::<Block, RuntimeApi, Executor>(&config)?;
     let client = Arc::new(client);
 
+    consensus::check_signature_scheme::<_, _, SingletonAuthorityPair>(&*client)
+        .map_err(|err| ServiceError::Other(err.to_string()))?;
+
     let select_chain = sc_consensus::LongestChain::new(backend.clone());
 
     let pool_api =
@@ -51,19 +356,95 @@ pub fn new_full_params(
         client.clone(),
     );
 
-    let singleton_config = consensus::SingletonConfig {
-        block_authority: sp_keyring::sr25519::Keyring::Alice.public().into(),
-        finality_authority: sp_keyring::sr25519::Keyring::Bob.public().into(),
+    // the authorities are read from the runtime at the parent of every block, as they rotate
+    // their session keys.
+    let config_at = consensus::runtime_config_at(client.clone(), allowed_engines());
+
+    // the runtime requires a timestamp inherent in every block, whichever engine authors it.
+    let inherent_data_providers = InherentDataProviders::new();
+    inherent_data_providers
+        .register_provider(sp_timestamp::InherentDataProvider)
+        .map_err(Into::into)
+        .map_err(sp_consensus::Error::InherentData)?;
+
+    // lets the runtime pay finality rewards once blocks are finalized by the singleton gadget.
+    if finality == Finality::Singleton {
+        inherent_data_providers
+            .register_provider(consensus::FinalityInherentDataProvider::new(client.clone()))
+            .map_err(Into::into)
+            .map_err(sp_consensus::Error::InherentData)?;
+    }
+
+    let (block_import, inner, justification_import, grandpa_link) = match finality {
+        Finality::Singleton => (
+            Box::new(client.clone()) as FullBlockImport,
+            Box::new(client.clone()) as FullBlockImport,
+            None,
+            None,
+        ),
+        Finality::Grandpa => {
+            let (grandpa_block_import, grandpa_link) = sc_finality_grandpa::block_import(
+                client.clone(),
+                &(client.clone() as Arc<_>),
+                select_chain.clone(),
+            )?;
+            let justification_import: BoxJustificationImport<Block> =
+                Box::new(grandpa_block_import.clone());
+
+            (
+                Box::new(grandpa_block_import.clone()) as FullBlockImport,
+                Box::new(grandpa_block_import) as FullBlockImport,
+                Some(justification_import),
+                Some(grandpa_link),
+            )
+        }
     };
 
-    let import_queue = consensus::import_queue(
-        singleton_config.clone(),
-        client.clone(),
-        client.clone(),
-        &task_manager.spawn_handle(),
-    );
+    let import_queue = match authoring {
+        Authoring::Singleton => consensus::import_queue(
+            config_at.clone(),
+            inner,
+            justification_import,
+            None,
+            client.clone(),
+            &task_manager.spawn_handle(),
+        ),
+        Authoring::Aura => {
+            // blocks authored with Aura can still be finalized by the singleton finality gadget.
+            let finality_config_at = match finality {
+                Finality::Singleton => Some(config_at.clone()),
+                Finality::Grandpa => None,
+            };
+            let singleton_block_import =
+                consensus::SingletonBlockImport::<_, _, _, SingletonAuthorityPair>::new(
+                    inner,
+                    client.clone(),
+                    finality_config_at,
+                );
+            let aura_block_import = sc_consensus_aura::AuraBlockImport::<_, _, _, AuraPair>::new(
+                singleton_block_import,
+                client.clone(),
+            );
 
-    let params = sc_service::ServiceParams {
+            sc_consensus_aura::import_queue::<_, _, _, AuraPair, _>(
+                sc_consensus_aura::slot_duration(&*client)?,
+                aura_block_import,
+                justification_import,
+                None,
+                client.clone(),
+                inherent_data_providers.clone(),
+                &task_manager.spawn_handle(),
+                config.prometheus_registry(),
+            )?
+        }
+    };
+
+    let grandpa_finality_proof_provider = grandpa_link.as_ref().map(|_| {
+        let provider = client.clone() as Arc<dyn StorageAndProofProvider<_, _>>;
+        Arc::new(GrandpaFinalityProofProvider::new(backend.clone(), provider))
+    });
+
+    let mut params = sc_service::ServiceParams {
         backend,
         client,
         import_queue,
@@ -79,68 +460,253 @@ pub fn new_full_params(
         rpc_extensions_builder: Box::new(|_| ()),
     };
 
-    Ok((params, singleton_config, select_chain))
+    if let Some(provider) = grandpa_finality_proof_provider {
+        params.finality_proof_provider = Some(provider);
+    }
+
+    let consensus = FullConsensus {
+        config_at,
+        authoring,
+        select_chain,
+        block_import,
+        inherent_data_providers,
+        grandpa_link,
+    };
+
+    Ok((params, consensus))
 }
 
 /// Builds a new service for a full client.
+#[allow(clippy::too_many_arguments)]
 pub fn new_full(
     config: Configuration,
+    authoring: Option<Authoring>,
+    finality: Option<Finality>,
     finality_gadget: bool,
-    finality_gadget_validator: bool,
+    singleton_author: Option<String>,
+    singleton_pow: bool,
+    singleton_finality_key: Option<String>,
+    finality_confirmation_depth: u32,
+    max_unfinalized_depth: Option<u32>,
 ) -> Result<TaskManager, ServiceError> {
-    let (params, singleton_config, select_chain) = new_full_params(config)?;
+    // the finality gadget keeps `finality_confirmation_depth` blocks unfinalized, so a lower
+    // limit would pause block authoring for good.
+    if let Some(max_unfinalized_depth) = max_unfinalized_depth {
+        if max_unfinalized_depth < finality_confirmation_depth {
+            return Err(ServiceError::Other(format!(
+                "--max-unfinalized-depth {} is lower than --finality-confirmation-depth {}",
+                max_unfinalized_depth, finality_confirmation_depth,
+            )));
+        }
+    }
+
+    let (
+        params,
+        FullConsensus {
+            config_at,
+            authoring,
+            select_chain,
+            block_import,
+            inherent_data_providers,
+            grandpa_link,
+        },
+    ) = new_full_params(config, authoring, finality)?;
+
+    if authoring == Authoring::Aura && (singleton_author.is_some() || singleton_pow) {
+        return Err(ServiceError::Other(
+            "The singleton block author can't run alongside Aura".into(),
+        ));
+    }
 
-    let (role, prometheus_registry, client, transaction_pool) = {
+    if singleton_author.is_some() && singleton_pow {
+        return Err(ServiceError::Other(
+            "Blocks can't be both sealed with the singleton author key and mined".into(),
+        ));
+    }
+
+    if grandpa_link.is_some() && (finality_gadget || singleton_finality_key.is_some()) {
+        return Err(ServiceError::Other(
+            "The singleton finality gadget can't run alongside GRANDPA".into(),
+        ));
+    }
+
+    ensure_no_dev_keys(
+        &*params.config.chain_spec,
+        params.config.dev_key_seed.as_deref(),
+        &singleton_config,
+    )?;
+
+    let (
+        role,
+        force_authoring,
+        name,
+        enable_grandpa,
+        prometheus_registry,
+        client,
+        transaction_pool,
+        keystore,
+    ) = {
         let sc_service::ServiceParams {
             config,
             client,
             transaction_pool,
+            keystore,
             ..
         } = &params;
 
         (
             config.role.clone(),
+            config.force_authoring,
+            config.network.node_name.clone(),
+            !config.disable_grandpa,
             config.prometheus_registry().cloned(),
             client.clone(),
             transaction_pool.clone(),
+            keystore.clone(),
         )
     };
 
+    // fail before starting the network if either key can't be used, checking them against the
+    // current and queued authorities at the best block.
+    let best_block = BlockId::Hash(client.chain_info().best_hash);
+    let singleton_config = singleton_config(&*client, best_block)?;
+    let queued = queued_authorities(&*client, best_block)?;
+    let block_authority_key = singleton_author
+        .map(|public| load_author_key(&keystore, &singleton_config, &queued, &public))
+        .transpose()?;
+    let finality_gadget_authority_key = singleton_finality_key
+        .map(|public| load_finality_key(&keystore, &singleton_config, &queued, &public))
+        .transpose()?;
+
+    if singleton_pow && singleton_config.pow_difficulty.is_none() {
+        return Err(ServiceError::Other(
+            consensus::Error::PowDisabled.to_string(),
+        ));
+    }
+
     let ServiceComponents {
         task_manager,
         network,
+        telemetry_on_connect_sinks,
         ..
     } = sc_service::build(params)?;
 
-    if role.is_authority() {
-        let proposer = sc_basic_authorship::ProposerFactory::new(
-            client.clone(),
-            transaction_pool,
-            prometheus_registry.as_ref(),
-        );
+    match authoring {
+        Authoring::Singleton => {
+            let proposer = sc_basic_authorship::ProposerFactory::new(
+                client.clone(),
+                transaction_pool,
+                prometheus_registry.as_ref(),
+            );
 
-        consensus::start_singleton_block_author(
-            sp_keyring::sr25519::Keyring::Alice.pair().into(),
-            client.clone(),
-            client.clone(),
-            proposer,
-            select_chain,
-            network.clone(),
-        );
+            if let Some(block_authority_key) = block_authority_key {
+                consensus::start_singleton_block_author(
+                    config_at.clone(),
+                    block_authority_key,
+                    max_unfinalized_depth,
+                    block_import,
+                    client.clone(),
+                    proposer,
+                    select_chain,
+                    network.clone(),
+                    inherent_data_providers.clone(),
+                    prometheus_registry.as_ref(),
+                    Arc::new(consensus::SystemClock),
+                )
+                .map_err(|err| ServiceError::Other(err.to_string()))?;
+            } else if singleton_pow {
+                consensus::start_singleton_pow_author(
+                    &singleton_config,
+                    block_import,
+                    proposer,
+                    select_chain,
+                    network.clone(),
+                    inherent_data_providers.clone(),
+                    Arc::new(consensus::SystemClock),
+                )
+                .map_err(|err| ServiceError::Other(err.to_string()))?;
+            }
+        }
+        Authoring::Aura => {
+            if role.is_authority() {
+                let proposer = sc_basic_authorship::ProposerFactory::new(
+                    client.clone(),
+                    transaction_pool,
+                    prometheus_registry.as_ref(),
+                );
+
+                let can_author_with =
+                    sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone());
+
+                let aura = sc_consensus_aura::start_aura::<_, _, _, _, _, AuraPair, _, _, _>(
+                    sc_consensus_aura::slot_duration(&*client)?,
+                    client.clone(),
+                    select_chain,
+                    block_import,
+                    proposer,
+                    network.clone(),
+                    inherent_data_providers.clone(),
+                    force_authoring,
+                    keystore.clone(),
+                    can_author_with,
+                )?;
+
+                // the Aura authoring task is considered essential, i.e. if it fails we take down
+                // the service with it.
+                task_manager
+                    .spawn_essential_handle()
+                    .spawn_blocking("aura", aura);
+            }
+        }
     }
 
-    let finality_gadget_authority_key = if finality_gadget_validator {
-        Some(sp_keyring::sr25519::Keyring::Bob.pair().into())
-    } else {
-        None
-    };
+    if let Some(grandpa_link) = grandpa_link {
+        let grandpa_config = sc_finality_grandpa::Config {
+            gossip_duration: Duration::from_millis(333),
+            justification_period: 512,
+            name: Some(name),
+            observer_enabled: false,
+            // only authorities need the keystore to vote.
+            keystore: if role.is_authority() {
+                Some(keystore as sp_core::traits::BareCryptoStorePtr)
+            } else {
+                None
+            },
+            is_authority: role.is_network_authority(),
+        };
+
+        if enable_grandpa {
+            let grandpa_params = sc_finality_grandpa::GrandpaParams {
+                config: grandpa_config,
+                link: grandpa_link,
+                network: network.clone(),
+                inherent_data_providers,
+                telemetry_on_connect: Some(telemetry_on_connect_sinks.on_connect_stream()),
+                voting_rule: sc_finality_grandpa::VotingRulesBuilder::default().build(),
+                prometheus_registry,
+                shared_voter_state: SharedVoterState::empty(),
+            };
+
+            task_manager.spawn_essential_handle().spawn_blocking(
+                "grandpa-voter",
+                sc_finality_grandpa::run_grandpa_voter(grandpa_params)?,
+            );
+        } else {
+            sc_finality_grandpa::setup_disabled_grandpa(
+                client.clone(),
+                &inherent_data_providers,
+                network.clone(),
+            )?;
+        }
+    }
 
-    if finality_gadget || finality_gadget_validator {
+    if finality_gadget || finality_gadget_authority_key.is_some() {
         task_manager.spawn_essential_handle().spawn_blocking(
             "singleton-finality-gadget",
             consensus::start_singleton_finality_gadget(
-                singleton_config,
+                config_at,
                 finality_gadget_authority_key,
+                finality_confirmation_depth,
                 client.clone(),
                 network.clone(),
                 network.clone(),
@@ -158,8 +724,25 @@ pub fn new_full(
     Ok(task_manager)
 }
 
-/// Builds a new service for a light client.
-pub fn new_light(config: Configuration) -> Result<TaskManager, ServiceError> {
+/// Builds a new service for a light client, which only supports networks authored with Aura and
+/// finalized with GRANDPA.
+pub fn new_light(
+    config: Configuration,
+    authoring: Option<Authoring>,
+    finality: Option<Finality>,
+) -> Result<TaskManager, ServiceError> {
+    let authoring = select(authoring, &config, AUTHORING_PROPERTY, Authoring::Singleton)?;
+    let finality = select(finality, &config, FINALITY_PROPERTY, Finality::Singleton)?;
+
+    // the light client can only execute runtime calls locally against the genesis state, so it
+    // can't follow the singleton authorities as they rotate their keys.
+    if authoring == Authoring::Singleton || finality == Finality::Singleton {
+        return Err(ServiceError::Other(
+            "Light clients only support Aura and GRANDPA, not singleton authoring or finality"
+                .into(),
+        ));
+    }
+
     let (client, backend, keystore, task_manager, on_demand) =
         sc_service::new_light_parts::<Block, RuntimeApi, Executor>(&config)?;
 
@@ -174,22 +757,37 @@ pub fn new_light(config: Configuration) -> Result<TaskManager, ServiceError> {
         task_manager.spawn_handle(),
     );
 
-    let singleton_config = consensus::SingletonConfig {
-        block_authority: sp_keyring::sr25519::Keyring::Alice.public().into(),
-        finality_authority: sp_keyring::sr25519::Keyring::Bob.public().into(),
-    };
-
-    let import_queue = consensus::import_queue(
-        singleton_config,
+    let grandpa_block_import = sc_finality_grandpa::light_block_import(
         client.clone(),
+        backend.clone(),
+        &(client.clone() as Arc<_>),
+        Arc::new(on_demand.checker().clone()) as Arc<_>,
+    )?;
+    let finality_proof_request_builder =
+        grandpa_block_import.create_finality_proof_request_builder();
+    let finality_proof_import: sp_consensus::import_queue::BoxFinalityProofImport<Block> =
+        Box::new(grandpa_block_import.clone());
+
+    let import_queue = sc_consensus_aura::import_queue::<_, _, _, AuraPair, _>(
+        sc_consensus_aura::slot_duration(&*client)?,
+        grandpa_block_import,
+        None,
+        Some(finality_proof_import),
         client.clone(),
+        InherentDataProviders::new(),
         &task_manager.spawn_handle(),
-    );
+        config.prometheus_registry(),
+    )?;
+
+    let finality_proof_provider = Arc::new(GrandpaFinalityProofProvider::new(
+        backend.clone(),
+        client.clone() as Arc<_>,
+    ));
 
-    sc_service::build(sc_service::ServiceParams {
+    let params = sc_service::ServiceParams {
         block_announce_validator_builder: None,
-        finality_proof_request_builder: None,
-        finality_proof_provider: None,
+        finality_proof_request_builder: Some(finality_proof_request_builder),
+        finality_proof_provider: Some(finality_proof_provider),
         on_demand: Some(on_demand),
         remote_blockchain: Some(backend.remote_blockchain()),
         rpc_extensions_builder: Box::new(|_| ()),
@@ -200,6 +798,49 @@ pub fn new_light(config: Configuration) -> Result<TaskManager, ServiceError> {
         keystore,
         backend,
         task_manager,
-    })
-    .map(|ServiceComponents { task_manager, .. }| task_manager)
+    };
+
+    sc_service::build(params).map(|ServiceComponents { task_manager, .. }| task_manager)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_spec;
+
+    /// Singleton configuration whose block authority is `block_authority`, and whose finality
+    /// authority isn't a development key.
+    fn config_with_author(
+        block_authority: sp_core::sr25519::Public,
+    ) -> consensus::SingletonConfig<SingletonAuthorityPair> {
+        let finality_authority = sp_core::sr25519::Public::from_raw([2; 32]);
+        let block_time = Duration::from_millis(consensus::DEFAULT_BLOCK_TIME);
+
+        consensus::SingletonConfig {
+            block_authority: SingletonPublic::from(block_authority).into(),
+            backup_authorities: Vec::new(),
+            block_time,
+            failover_delay: block_time * 3,
+            leader_election: consensus::LeaderElection::Fixed,
+            pow_difficulty: None,
+            finality_authority: SingletonPublic::from(finality_authority).into(),
+            allowed_engines: allowed_engines(),
+        }
+    }
+
+    #[test]
+    fn live_chains_refuse_development_keys() {
+        let staging = chain_spec::staging_config().unwrap();
+        let development = chain_spec::development_config().unwrap();
+        let alice = config_with_author(Sr25519Keyring::Alice.public());
+        // an operator's key, whose secret isn't known.
+        let staging_authority = config_with_author(sp_core::sr25519::Public::from_raw([1; 32]));
+
+        assert!(ensure_no_dev_keys(&staging, None, &alice).is_err());
+        assert!(ensure_no_dev_keys(&staging, Some("//Alice"), &staging_authority).is_err());
+        assert!(ensure_no_dev_keys(&staging, None, &staging_authority).is_ok());
+
+        // development chains are expected to use them.
+        assert!(ensure_no_dev_keys(&development, Some("//Alice"), &alice).is_ok());
+    }
 }
@@ -0,0 +1,153 @@
+//! # Rewards Pallet
+//!
+//! Mints a fixed reward for every authored block, paid to the block author as reported by
+//! `pallet_authorship`. Optionally a share of each reward goes to the finality authority.
+//!
+//! The finality authority's share of a block's reward is only paid once the runtime learns that
+//! the block is finalized, through `OnFinality` (see the singleton pallet), to the account of the
+//! finality authority at that time. Until then it is kept as an unpaid reward of the block, which
+//! is lost if the block never gets finalized. Only the `MaxUnpaidFinalityRewards` latest unpaid
+//! rewards are kept, e.g. as blocks aren't finalized by singleton finality justifications when
+//! the nodes run GRANDPA.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use consensus_primitives::OnFinality;
+use frame_support::{
+    decl_event, decl_module, decl_storage,
+    traits::{Currency, Get, Imbalance},
+    weights::Weight,
+};
+use sp_runtime::{
+    traits::{Saturating, UniqueSaturatedInto, Zero},
+    Perbill,
+};
+use sp_std::prelude::*;
+
+#[cfg(test)]
+mod mock;
+
+#[cfg(test)]
+mod tests;
+
+/// Weight of paying the finality reward share of one block, on top of the storage accesses.
+const FINALITY_REWARD_WEIGHT: Weight = 1_000_000;
+
+type BalanceOf<T> =
+    <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+
+pub trait Trait: frame_system::Trait {
+    /// The currency in which rewards are minted.
+    type Currency: Currency<Self::AccountId>;
+
+    /// The account of the current finality authority, if any, receiving `FinalityRewardShare` of
+    /// each block reward.
+    type FinalityAuthority: Get<Option<Self::AccountId>>;
+
+    /// The most blocks whose finality reward share is kept until they are finalized.
+    type MaxUnpaidFinalityRewards: Get<u32>;
+
+    /// The overarching event type.
+    type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Rewards {
+        /// The amount minted for each authored block.
+        BlockReward get(fn block_reward) config(): BalanceOf<T>;
+        /// The share of each block reward paid to the finality authority.
+        FinalityRewardShare get(fn finality_reward_share) config(): Perbill;
+        /// The finality authority's share of the reward of each block that isn't known to be
+        /// finalized yet, in block order, at most `MaxUnpaidFinalityRewards` of them.
+        UnpaidFinalityRewards get(fn unpaid_finality_rewards): Vec<(T::BlockNumber, BalanceOf<T>)>;
+    }
+}
+
+decl_event!(
+    pub enum Event<T>
+    where
+        AccountId = <T as frame_system::Trait>::AccountId,
+        Balance = BalanceOf<T>,
+    {
+        /// A block reward was paid. [who, amount]
+        Rewarded(AccountId, Balance),
+    }
+);
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event() = default;
+    }
+}
+
+impl<T: Trait> Module<T> {
+    fn reward(who: &T::AccountId, amount: BalanceOf<T>) {
+        let imbalance = T::Currency::deposit_creating(who, amount);
+
+        if !imbalance.peek().is_zero() {
+            Self::deposit_event(RawEvent::Rewarded(who.clone(), imbalance.peek()));
+        }
+    }
+}
+
+impl<T: Trait> pallet_authorship::EventHandler<T::AccountId, T::BlockNumber> for Module<T> {
+    fn note_author(author: T::AccountId) {
+        let reward = Self::block_reward();
+
+        let finality_reward = match T::FinalityAuthority::get() {
+            Some(_) => Self::finality_reward_share() * reward,
+            None => Zero::zero(),
+        };
+
+        if !finality_reward.is_zero() {
+            let number = <frame_system::Module<T>>::block_number();
+            <UnpaidFinalityRewards<T>>::mutate(|unpaid| {
+                unpaid.push((number, finality_reward));
+
+                // the oldest rewards are lost.
+                let excess = unpaid
+                    .len()
+                    .saturating_sub(T::MaxUnpaidFinalityRewards::get() as usize);
+                unpaid.drain(..excess);
+            });
+        }
+
+        Self::reward(&author, reward.saturating_sub(finality_reward));
+    }
+
+    fn note_uncle(_author: T::AccountId, _age: T::BlockNumber) {}
+}
+
+impl<T: Trait> OnFinality<T::BlockNumber> for Module<T> {
+    fn on_finality(finalized: T::BlockNumber) {
+        let mut unpaid = Self::unpaid_finality_rewards();
+        let finalized_count = unpaid
+            .iter()
+            .take_while(|(number, _)| *number <= finalized)
+            .count();
+        <UnpaidFinalityRewards<T>>::put(unpaid.split_off(finalized_count));
+
+        let finality_reward = unpaid
+            .into_iter()
+            .fold(Zero::zero(), |total: BalanceOf<T>, (_, reward)| {
+                total.saturating_add(reward)
+            });
+
+        if let Some(finality_authority) = T::FinalityAuthority::get() {
+            Self::reward(&finality_authority, finality_reward);
+        }
+    }
+
+    fn on_finality_weight(number: T::BlockNumber, last_finalized: T::BlockNumber) -> Weight {
+        // there is at most one unpaid reward per block.
+        let max_paid: T::BlockNumber = T::MaxUnpaidFinalityRewards::get().into();
+        let paid: u64 = number
+            .saturating_sub(last_finalized)
+            .min(max_paid)
+            .unique_saturated_into();
+
+        T::DbWeight::get()
+            .reads_writes(2, 2)
+            .saturating_add(paid.saturating_mul(FINALITY_REWARD_WEIGHT))
+    }
+}
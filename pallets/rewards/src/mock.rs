@@ -0,0 +1,130 @@
+use crate::{Module, Trait};
+use frame_support::{
+    impl_outer_event, impl_outer_origin, parameter_types, traits::Get, weights::Weight,
+};
+use frame_system as system;
+use sp_core::H256;
+use sp_runtime::{
+    testing::Header,
+    traits::{BlakeTwo256, IdentityLookup},
+    Perbill,
+};
+use std::cell::RefCell;
+
+impl_outer_origin! {
+    pub enum Origin for Test {}
+}
+
+mod rewards {
+    pub use crate::Event;
+}
+
+impl_outer_event! {
+    pub enum TestEvent for Test {
+        system<T>,
+        pallet_balances<T>,
+        rewards<T>,
+    }
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Test;
+parameter_types! {
+    pub const BlockHashCount: u64 = 250;
+    pub const MaximumBlockWeight: Weight = 1024;
+    pub const MaximumBlockLength: u32 = 2 * 1024;
+    pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+    pub const ExistentialDeposit: u64 = 1;
+}
+
+impl system::Trait for Test {
+    type BaseCallFilter = ();
+    type Origin = Origin;
+    type Call = ();
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = TestEvent;
+    type BlockHashCount = BlockHashCount;
+    type MaximumBlockWeight = MaximumBlockWeight;
+    type DbWeight = ();
+    type BlockExecutionWeight = ();
+    type ExtrinsicBaseWeight = ();
+    type MaximumExtrinsicWeight = MaximumBlockWeight;
+    type MaximumBlockLength = MaximumBlockLength;
+    type AvailableBlockRatio = AvailableBlockRatio;
+    type Version = ();
+    type ModuleToIndex = ();
+    type AccountData = pallet_balances::AccountData<u64>;
+    type OnNewAccount = ();
+    type OnKilledAccount = ();
+    type SystemWeightInfo = ();
+}
+
+impl pallet_balances::Trait for Test {
+    type Balance = u64;
+    type Event = TestEvent;
+    type DustRemoval = ();
+    type ExistentialDeposit = ExistentialDeposit;
+    type AccountStore = System;
+    type WeightInfo = ();
+}
+
+thread_local! {
+    static FINALITY_AUTHORITY: RefCell<Option<u64>> = RefCell::new(None);
+}
+
+/// The finality authority's account, which tests may change.
+pub struct FinalityAuthority;
+
+impl Get<Option<u64>> for FinalityAuthority {
+    fn get() -> Option<u64> {
+        FINALITY_AUTHORITY.with(|finality_authority| *finality_authority.borrow())
+    }
+}
+
+pub fn set_finality_authority(account: Option<u64>) {
+    FINALITY_AUTHORITY.with(|finality_authority| *finality_authority.borrow_mut() = account);
+}
+
+parameter_types! {
+    pub const MaxUnpaidFinalityRewards: u32 = 3;
+}
+
+impl Trait for Test {
+    type Currency = Balances;
+    type Event = TestEvent;
+    type FinalityAuthority = FinalityAuthority;
+    type MaxUnpaidFinalityRewards = MaxUnpaidFinalityRewards;
+}
+
+pub type System = system::Module<Test>;
+pub type Balances = pallet_balances::Module<Test>;
+pub type Rewards = Module<Test>;
+
+pub fn new_test_ext(
+    block_reward: u64,
+    finality_authority: Option<u64>,
+    finality_reward_share: Perbill,
+) -> sp_io::TestExternalities {
+    let mut storage = system::GenesisConfig::default()
+        .build_storage::<Test>()
+        .unwrap();
+
+    crate::GenesisConfig::<Test> {
+        block_reward,
+        finality_reward_share,
+    }
+    .assimilate_storage(&mut storage)
+    .unwrap();
+
+    set_finality_authority(finality_authority);
+
+    let mut ext: sp_io::TestExternalities = storage.into();
+    ext.execute_with(|| System::set_block_number(1));
+    ext
+}
@@ -0,0 +1,109 @@
+use crate::{mock::*, RawEvent};
+use consensus_primitives::OnFinality;
+use pallet_authorship::EventHandler;
+use sp_runtime::Perbill;
+
+fn rewarded_events() -> Vec<(u64, u64)> {
+    System::events()
+        .into_iter()
+        .filter_map(|record| match record.event {
+            TestEvent::rewards(RawEvent::Rewarded(who, amount)) => Some((who, amount)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn block_author_receives_full_reward_without_finality_authority() {
+    new_test_ext(100, None, Perbill::from_percent(20)).execute_with(|| {
+        Rewards::note_author(1);
+
+        assert_eq!(Balances::free_balance(1), 100);
+        assert_eq!(Balances::total_issuance(), 100);
+        assert_eq!(rewarded_events(), vec![(1, 100)]);
+    });
+}
+
+#[test]
+fn finality_authority_receives_share_of_reward_once_finalized() {
+    new_test_ext(100, Some(2), Perbill::from_percent(20)).execute_with(|| {
+        Rewards::note_author(1);
+        System::set_block_number(2);
+        Rewards::note_author(1);
+        System::set_block_number(3);
+        Rewards::note_author(1);
+
+        assert_eq!(Balances::free_balance(1), 240);
+        assert_eq!(Balances::free_balance(2), 0);
+        assert_eq!(
+            Rewards::unpaid_finality_rewards(),
+            vec![(1, 20), (2, 20), (3, 20)]
+        );
+
+        Rewards::on_finality(2);
+
+        assert_eq!(Balances::free_balance(2), 40);
+        assert_eq!(Rewards::unpaid_finality_rewards(), vec![(3, 20)]);
+        assert_eq!(rewarded_events().last(), Some(&(2, 40)));
+
+        // finalizing the same blocks again pays nothing.
+        Rewards::on_finality(2);
+        assert_eq!(Balances::free_balance(2), 40);
+
+        Rewards::on_finality(3);
+        assert_eq!(Balances::free_balance(2), 60);
+        assert_eq!(Balances::total_issuance(), 300);
+        assert!(Rewards::unpaid_finality_rewards().is_empty());
+    });
+}
+
+#[test]
+fn no_reward_is_minted_when_disabled() {
+    new_test_ext(0, Some(2), Perbill::from_percent(20)).execute_with(|| {
+        Rewards::note_author(1);
+
+        assert_eq!(Balances::total_issuance(), 0);
+        assert!(rewarded_events().is_empty());
+    });
+}
+
+#[test]
+fn finality_reward_is_paid_to_current_finality_authority() {
+    new_test_ext(100, Some(2), Perbill::from_percent(20)).execute_with(|| {
+        Rewards::note_author(1);
+
+        // the finality authority rotated its keys to another validator's.
+        set_finality_authority(Some(3));
+        Rewards::on_finality(1);
+
+        assert_eq!(Balances::free_balance(2), 0);
+        assert_eq!(Balances::free_balance(3), 20);
+    });
+}
+
+#[test]
+fn only_latest_unpaid_finality_rewards_are_kept() {
+    new_test_ext(100, Some(2), Perbill::from_percent(20)).execute_with(|| {
+        for number in 1..=4 {
+            System::set_block_number(number);
+            Rewards::note_author(1);
+        }
+
+        assert_eq!(
+            Rewards::unpaid_finality_rewards(),
+            vec![(2, 20), (3, 20), (4, 20)]
+        );
+
+        // the share of the first block is lost.
+        Rewards::on_finality(4);
+        assert_eq!(Balances::free_balance(2), 60);
+        assert_eq!(Balances::total_issuance(), 380);
+
+        // paying more rewards weighs more, up to the kept ones.
+        assert!(Rewards::on_finality_weight(2, 1) < Rewards::on_finality_weight(4, 1));
+        assert_eq!(
+            Rewards::on_finality_weight(100, 1),
+            Rewards::on_finality_weight(4, 1)
+        );
+    });
+}
@@ -0,0 +1,352 @@
+//! # Singleton Pallet
+//!
+//! Keeps track of the singleton block authorities and the finality authority, and identifies the
+//! author of each block from the pre-runtime digest added by the singleton block author, so that
+//! other pallets (e.g. `pallet_authorship`) can attribute blocks to it.
+//!
+//! When used with `pallet_session`, the block authorities are the block authority keys of the
+//! session validators, in order, and the finality authority is the finality key of the first
+//! validator, whose account `FinalityKeys` provides (e.g. to pay it rewards). Both are updated
+//! whenever the validators rotate their session keys.
+//!
+//! The leader election mode, the block time and the proof of work difficulty are set in the
+//! genesis config.
+//!
+//! Finality happens outside of the runtime, so the block author includes the last finality
+//! justification it knows of as an inherent. The pallet checks it against the finality authority
+//! and notifies `OnFinality` (e.g. the rewards pallet) that blocks up to the justified one are
+//! finalized.
+//!
+//! The timestamp of singleton blocks is set twice: in the pre-runtime digest, where the consensus
+//! engine checks it, and in the timestamp inherent. As `OnTimestampSet` handler, the pallet
+//! requires both to be the same.
+//!
+//! The VRF outputs included by block authors with VRF leader election are accumulated into the
+//! randomness of the next epoch, which starts with each new session.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Decode, Encode};
+use consensus_primitives::{
+    AuthorityIndex, LeaderElection, OnFinality, PowPreDigest, PreDigest, Randomness,
+    DEFAULT_BLOCK_TIME, FINALITY_INHERENT_IDENTIFIER,
+};
+use frame_support::{
+    decl_error, decl_module, decl_storage, ensure,
+    traits::{FindAuthor, Get},
+    weights::{DispatchClass, Weight},
+    Parameter,
+};
+use frame_system::ensure_none;
+use sp_inherents::{InherentData, InherentIdentifier, MakeFatalError, ProvideInherent};
+use sp_runtime::{
+    traits::{Member, UniqueSaturatedInto, Zero},
+    BoundToRuntimeAppPublic, ConsensusEngineId, RuntimeAppPublic,
+};
+use sp_std::{marker::PhantomData, prelude::*};
+use sp_timestamp::OnTimestampSet;
+
+#[cfg(test)]
+mod mock;
+
+#[cfg(test)]
+mod tests;
+
+pub trait Trait: frame_system::Trait {
+    /// The identifier type for a singleton block authority.
+    type AuthorityId: Member + Parameter + RuntimeAppPublic + Default;
+
+    /// The identifier type for the singleton finality authority.
+    type FinalityId: Member + Parameter + RuntimeAppPublic + Default;
+
+    /// Handler notified when blocks are proven to be finalized.
+    type OnFinality: OnFinality<Self::BlockNumber>;
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Singleton {
+        /// The block authorities, indexed by the `authority_index` of the pre-runtime digest.
+        Authorities get(fn authorities): Vec<T::AuthorityId>;
+        /// The block authorities queued for the next session.
+        NextAuthorities get(fn next_authorities): Vec<T::AuthorityId>;
+        /// The finality authority.
+        FinalityAuthority get(fn finality_authority): Option<T::FinalityId>;
+        /// The finality authority queued for the next session.
+        NextFinalityAuthority get(fn next_finality_authority): Option<T::FinalityId>;
+        /// The account of the session validator whose finality key is the finality authority.
+        FinalityAuthorityAccount get(fn finality_authority_account): Option<T::AccountId>;
+        /// How the block authority allowed to seal each block is chosen.
+        LeaderElectionMode get(fn leader_election) config(): LeaderElection;
+        /// Time between blocks in milliseconds, set at genesis.
+        BlockTime get(fn block_time) config(): u64 = DEFAULT_BLOCK_TIME;
+        /// Difficulty of proof of work seals, if anyone may also seal blocks with proof of work.
+        PowDifficulty get(fn pow_difficulty): Option<u128>;
+        /// Randomness of the current epoch, used for VRF leader election.
+        EpochRandomness get(fn randomness): Randomness;
+        /// Randomness accumulated from the VRF outputs of blocks so far, which becomes the epoch
+        /// randomness at the next session.
+        NextRandomness get(fn next_randomness): Randomness;
+        /// The highest block proven to be finalized by a finality justification.
+        LastFinalized get(fn last_finalized): T::BlockNumber;
+    }
+    add_extra_genesis {
+        config(authorities): Vec<T::AuthorityId>;
+        config(finality_authority): Option<T::FinalityId>;
+        config(pow_difficulty): Option<u128>;
+        build(|config| {
+            Module::<T>::initialize_authorities(&config.authorities);
+            Module::<T>::initialize_finality_authority(config.finality_authority.as_ref());
+
+            if let Some(pow_difficulty) = config.pow_difficulty {
+                PowDifficulty::put(pow_difficulty);
+            }
+        })
+    }
+}
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// The justified block is not later than the last finalized block.
+        StaleFinality,
+        /// The justified block is not a known ancestor of the current block.
+        UnknownBlock,
+        /// There is no finality authority to check the justification against.
+        NoFinalityAuthority,
+        /// The justification is not a valid signature by the finality authority.
+        BadJustification,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        /// Notes that the blocks up to `number` are finalized, as proven by the finality
+        /// authority's `justification` of block `number`.
+        ///
+        /// The dispatch origin for this call must be `Inherent`.
+        #[weight = (
+            T::DbWeight::get().reads_writes(4, 1).saturating_add(
+                T::OnFinality::on_finality_weight(*number, Module::<T>::last_finalized()),
+            ),
+            DispatchClass::Mandatory,
+        )]
+        fn note_finality(origin, number: T::BlockNumber, justification: Vec<u8>) {
+            ensure_none(origin)?;
+            Self::check_finality(number, &justification)?;
+
+            <LastFinalized<T>>::put(number);
+            T::OnFinality::on_finality(number);
+        }
+
+        fn on_initialize(_n: T::BlockNumber) -> Weight {
+            if Self::deposit_vrf_output() {
+                T::DbWeight::get().reads_writes(2, 1)
+            } else {
+                T::DbWeight::get().reads(1)
+            }
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    fn initialize_authorities(authorities: &[T::AuthorityId]) {
+        if !authorities.is_empty() {
+            assert!(
+                <Authorities<T>>::get().is_empty(),
+                "Authorities are already initialized!"
+            );
+            <Authorities<T>>::put(authorities);
+            <NextAuthorities<T>>::put(authorities);
+        }
+    }
+
+    fn initialize_finality_authority(finality_authority: Option<&T::FinalityId>) {
+        if let Some(finality_authority) = finality_authority {
+            assert!(
+                <FinalityAuthority<T>>::get().is_none(),
+                "Finality authority is already initialized!"
+            );
+            <FinalityAuthority<T>>::put(finality_authority);
+            <NextFinalityAuthority<T>>::put(finality_authority);
+        }
+    }
+
+    /// Checks that `justification` is a signature of block `number` by the finality authority,
+    /// and that the block was finalized after the last one noted.
+    fn check_finality(number: T::BlockNumber, justification: &[u8]) -> Result<(), Error<T>> {
+        ensure!(number > Self::last_finalized(), Error::<T>::StaleFinality);
+
+        // unknown and pruned block hashes are zero.
+        let hash = <frame_system::Module<T>>::block_hash(number);
+        ensure!(
+            !number.is_zero() && hash != Default::default(),
+            Error::<T>::UnknownBlock
+        );
+
+        let finality_authority =
+            Self::finality_authority().ok_or(Error::<T>::NoFinalityAuthority)?;
+        let signature =
+            Decode::decode(&mut &justification[..]).map_err(|_| Error::<T>::BadJustification)?;
+        ensure!(
+            finality_authority.verify(&hash, &signature),
+            Error::<T>::BadJustification
+        );
+
+        Ok(())
+    }
+
+    /// Mixes the VRF output of the current block's pre-runtime digest, if any, into the
+    /// randomness of the next epoch. Returns whether there was one.
+    fn deposit_vrf_output() -> bool {
+        let digest = <frame_system::Module<T>>::digest();
+        let pre_runtime_digests = digest.logs.iter().filter_map(|item| item.as_pre_runtime());
+
+        let vrf = match PreDigest::find(pre_runtime_digests).and_then(|pre_digest| pre_digest.vrf) {
+            Some(vrf) => vrf,
+            None => return false,
+        };
+
+        let next_randomness =
+            (NextRandomness::get(), vrf.output).using_encoded(sp_io::hashing::blake2_256);
+        NextRandomness::put(next_randomness);
+
+        true
+    }
+
+    /// Returns the authority that authored the current block, if known.
+    pub fn author() -> Option<T::AuthorityId> {
+        let digest = <frame_system::Module<T>>::digest();
+        let pre_runtime_digests = digest.logs.iter().filter_map(|item| item.as_pre_runtime());
+
+        Self::find_author(pre_runtime_digests)
+            .and_then(|index| Self::authorities().get(index as usize).cloned())
+    }
+}
+
+impl<T: Trait> FindAuthor<AuthorityIndex> for Module<T> {
+    fn find_author<'a, I>(digests: I) -> Option<AuthorityIndex>
+    where
+        I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+    {
+        PreDigest::find(digests).map(|pre_digest| pre_digest.authority_index)
+    }
+}
+
+impl<T: Trait, Moment: UniqueSaturatedInto<u64>> OnTimestampSet<Moment> for Module<T> {
+    /// Checks that the timestamp inherent matches the timestamp of the singleton or proof of work
+    /// pre-runtime digest, if any, as the consensus engine only checks the latter.
+    fn on_timestamp_set(moment: Moment) {
+        let digest = <frame_system::Module<T>>::digest();
+        let pre_runtime_digests = || digest.logs.iter().filter_map(|item| item.as_pre_runtime());
+
+        let timestamp = PreDigest::find(pre_runtime_digests())
+            .map(|pre_digest| pre_digest.timestamp)
+            .or_else(|| {
+                PowPreDigest::find(pre_runtime_digests()).map(|pre_digest| pre_digest.timestamp)
+            });
+
+        if let Some(timestamp) = timestamp {
+            assert_eq!(
+                moment.unique_saturated_into(),
+                timestamp,
+                "Timestamp inherent must match the singleton pre-runtime digest"
+            );
+        }
+    }
+}
+
+impl<T: Trait> ProvideInherent for Module<T> {
+    type Call = Call<T>;
+    type Error = MakeFatalError<()>;
+    const INHERENT_IDENTIFIER: InherentIdentifier = FINALITY_INHERENT_IDENTIFIER;
+
+    fn create_inherent(data: &InherentData) -> Option<Self::Call> {
+        let (number, justification) = data
+            .get_data::<(T::BlockNumber, Vec<u8>)>(&FINALITY_INHERENT_IDENTIFIER)
+            .ok()??;
+
+        // the finality is only worth noting if it's new and valid, otherwise the block would be
+        // rejected.
+        Self::check_finality(number, &justification).ok()?;
+
+        Some(Call::note_finality(number, justification))
+    }
+}
+
+impl<T: Trait> BoundToRuntimeAppPublic for Module<T> {
+    type Public = T::AuthorityId;
+}
+
+impl<T: Trait> pallet_session::OneSessionHandler<T::AccountId> for Module<T> {
+    type Key = T::AuthorityId;
+
+    fn on_genesis_session<'a, I: 'a>(validators: I)
+    where
+        I: Iterator<Item = (&'a T::AccountId, T::AuthorityId)>,
+    {
+        let authorities = validators.map(|(_, key)| key).collect::<Vec<_>>();
+        Self::initialize_authorities(&authorities);
+    }
+
+    fn on_new_session<'a, I: 'a>(changed: bool, validators: I, queued_validators: I)
+    where
+        I: Iterator<Item = (&'a T::AccountId, T::AuthorityId)>,
+    {
+        EpochRandomness::put(NextRandomness::get());
+
+        if changed {
+            <Authorities<T>>::put(validators.map(|(_, key)| key).collect::<Vec<_>>());
+        }
+
+        // the queued keys may change in any session, e.g. with `set_keys`.
+        <NextAuthorities<T>>::put(queued_validators.map(|(_, key)| key).collect::<Vec<_>>());
+    }
+
+    fn on_disabled(_validator_index: usize) {}
+}
+
+/// Session handler keeping track of the finality authority key, which is the finality key of the
+/// first session validator. Also provides the account of that validator.
+pub struct FinalityKeys<T>(PhantomData<T>);
+
+impl<T: Trait> Get<Option<T::AccountId>> for FinalityKeys<T> {
+    fn get() -> Option<T::AccountId> {
+        Module::<T>::finality_authority_account()
+    }
+}
+
+impl<T: Trait> BoundToRuntimeAppPublic for FinalityKeys<T> {
+    type Public = T::FinalityId;
+}
+
+impl<T: Trait> pallet_session::OneSessionHandler<T::AccountId> for FinalityKeys<T> {
+    type Key = T::FinalityId;
+
+    fn on_genesis_session<'a, I: 'a>(mut validators: I)
+    where
+        I: Iterator<Item = (&'a T::AccountId, T::FinalityId)>,
+    {
+        if let Some((account, key)) = validators.next() {
+            Module::<T>::initialize_finality_authority(Some(&key));
+            <FinalityAuthorityAccount<T>>::put(account.clone());
+        }
+    }
+
+    fn on_new_session<'a, I: 'a>(changed: bool, mut validators: I, mut queued_validators: I)
+    where
+        I: Iterator<Item = (&'a T::AccountId, T::FinalityId)>,
+    {
+        <NextFinalityAuthority<T>>::set(queued_validators.next().map(|(_, key)| key));
+
+        if changed {
+            let first = validators.next();
+            <FinalityAuthorityAccount<T>>::set(
+                first.as_ref().map(|(account, _)| (*account).clone()),
+            );
+            <FinalityAuthority<T>>::set(first.map(|(_, key)| key));
+        }
+    }
+
+    fn on_disabled(_validator_index: usize) {}
+}
@@ -0,0 +1,101 @@
+use crate::{Module, Trait};
+use consensus_primitives::OnFinality;
+use frame_support::{impl_outer_origin, parameter_types, weights::Weight};
+use frame_system as system;
+use sp_core::H256;
+use sp_runtime::{
+    testing::{Header, UintAuthorityId},
+    traits::{BlakeTwo256, IdentityLookup},
+    Perbill,
+};
+use std::cell::RefCell;
+
+impl_outer_origin! {
+    pub enum Origin for Test {}
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Test;
+parameter_types! {
+    pub const BlockHashCount: u64 = 250;
+    pub const MaximumBlockWeight: Weight = 1024;
+    pub const MaximumBlockLength: u32 = 2 * 1024;
+    pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+}
+
+impl system::Trait for Test {
+    type BaseCallFilter = ();
+    type Origin = Origin;
+    type Call = ();
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = ();
+    type BlockHashCount = BlockHashCount;
+    type MaximumBlockWeight = MaximumBlockWeight;
+    type DbWeight = ();
+    type BlockExecutionWeight = ();
+    type ExtrinsicBaseWeight = ();
+    type MaximumExtrinsicWeight = MaximumBlockWeight;
+    type MaximumBlockLength = MaximumBlockLength;
+    type AvailableBlockRatio = AvailableBlockRatio;
+    type Version = ();
+    type ModuleToIndex = ();
+    type AccountData = ();
+    type OnNewAccount = ();
+    type OnKilledAccount = ();
+    type SystemWeightInfo = ();
+}
+
+thread_local! {
+    static FINALIZED: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+}
+
+/// Records the block numbers that the pallet reports as finalized.
+pub struct RecordFinality;
+
+impl OnFinality<u64> for RecordFinality {
+    fn on_finality(number: u64) {
+        FINALIZED.with(|finalized| finalized.borrow_mut().push(number));
+    }
+
+    fn on_finality_weight(_number: u64, _last_finalized: u64) -> Weight {
+        0
+    }
+}
+
+pub fn finalized() -> Vec<u64> {
+    FINALIZED.with(|finalized| finalized.borrow().clone())
+}
+
+impl Trait for Test {
+    type AuthorityId = UintAuthorityId;
+    type FinalityId = UintAuthorityId;
+    type OnFinality = RecordFinality;
+}
+
+pub type System = system::Module<Test>;
+pub type Singleton = Module<Test>;
+
+pub fn new_test_ext(
+    authorities: Vec<u64>,
+    finality_authority: Option<u64>,
+) -> sp_io::TestExternalities {
+    let mut storage = system::GenesisConfig::default()
+        .build_storage::<Test>()
+        .unwrap();
+
+    crate::GenesisConfig::<Test> {
+        authorities: authorities.into_iter().map(UintAuthorityId).collect(),
+        finality_authority: finality_authority.map(UintAuthorityId),
+        ..Default::default()
+    }
+    .assimilate_storage(&mut storage)
+    .unwrap();
+
+    storage.into()
+}
@@ -0,0 +1,385 @@
+use crate::{mock::*, Call, Error, FinalityKeys};
+use codec::Decode;
+use codec::Encode;
+use consensus_primitives::{
+    LeaderElection, PowPreDigest, PreDigest, VrfSignature, DEFAULT_BLOCK_TIME,
+    FINALITY_INHERENT_IDENTIFIER, SINGLETON_ENGINE_ID, SINGLETON_POW_ENGINE_ID,
+};
+use frame_support::{
+    assert_noop, assert_ok,
+    traits::{FindAuthor, Get, OnInitialize},
+};
+use pallet_session::OneSessionHandler;
+use sp_core::H256;
+use sp_inherents::{InherentData, ProvideInherent};
+use sp_runtime::{
+    testing::{TestSignature, UintAuthorityId},
+    Digest, DigestItem,
+};
+use sp_timestamp::OnTimestampSet;
+
+fn initialize_block(number: u64, digest: Digest<sp_core::H256>) {
+    System::initialize(
+        &number,
+        &Default::default(),
+        &Default::default(),
+        &digest,
+        Default::default(),
+    );
+}
+
+fn pre_digest(authority_index: u32) -> DigestItem<sp_core::H256> {
+    vrf_pre_digest(authority_index, None)
+}
+
+fn vrf_pre_digest(authority_index: u32, vrf_output: Option<u8>) -> DigestItem<sp_core::H256> {
+    // the pallet doesn't check VRF proofs, so any output decodes and a zero proof is enough.
+    let vrf = vrf_output.map(|output| VrfSignature {
+        output: Decode::decode(&mut &[output; 32][..]).unwrap(),
+        proof: Decode::decode(&mut &[0; 64][..]).unwrap(),
+    });
+
+    DigestItem::PreRuntime(
+        SINGLETON_ENGINE_ID,
+        PreDigest {
+            authority_index,
+            timestamp: 0,
+            vrf,
+        }
+        .encode(),
+    )
+}
+
+#[test]
+fn authorities_are_initialized_from_genesis() {
+    new_test_ext(vec![1, 2], Some(3)).execute_with(|| {
+        assert_eq!(
+            Singleton::authorities(),
+            vec![UintAuthorityId(1), UintAuthorityId(2)]
+        );
+        assert_eq!(Singleton::finality_authority(), Some(UintAuthorityId(3)));
+    });
+}
+
+#[test]
+fn consensus_parameters_are_initialized_from_genesis() {
+    new_test_ext(vec![1], None).execute_with(|| {
+        assert_eq!(Singleton::leader_election(), LeaderElection::Fixed);
+        assert_eq!(Singleton::block_time(), DEFAULT_BLOCK_TIME);
+        assert_eq!(Singleton::pow_difficulty(), None);
+    });
+
+    let mut storage = frame_system::GenesisConfig::default()
+        .build_storage::<Test>()
+        .unwrap();
+
+    crate::GenesisConfig::<Test> {
+        leader_election: LeaderElection::Vrf,
+        block_time: 6_000,
+        pow_difficulty: Some(16),
+        ..Default::default()
+    }
+    .assimilate_storage(&mut storage)
+    .unwrap();
+
+    sp_io::TestExternalities::new(storage).execute_with(|| {
+        assert_eq!(Singleton::leader_election(), LeaderElection::Vrf);
+        assert_eq!(Singleton::block_time(), 6_000);
+        assert_eq!(Singleton::pow_difficulty(), Some(16));
+    });
+}
+
+#[test]
+fn finds_author_from_pre_runtime_digest() {
+    let digests = vec![
+        (*b"othr", &[1u8, 2, 3][..]),
+        (SINGLETON_ENGINE_ID, &[1, 0, 0, 0][..]),
+    ];
+
+    assert_eq!(Singleton::find_author(digests), Some(1));
+    assert_eq!(
+        Singleton::find_author(vec![(*b"othr", &[0u8; 4][..])]),
+        None
+    );
+    assert_eq!(
+        Singleton::find_author(vec![(SINGLETON_ENGINE_ID, &[0u8][..])]),
+        None
+    );
+}
+
+#[test]
+fn author_is_looked_up_in_authorities() {
+    new_test_ext(vec![1, 2], Some(3)).execute_with(|| {
+        initialize_block(
+            1,
+            Digest {
+                logs: vec![pre_digest(1)],
+            },
+        );
+        assert_eq!(Singleton::author(), Some(UintAuthorityId(2)));
+
+        initialize_block(
+            2,
+            Digest {
+                logs: vec![pre_digest(2)],
+            },
+        );
+        assert_eq!(Singleton::author(), None);
+
+        initialize_block(3, Digest::default());
+        assert_eq!(Singleton::author(), None);
+    });
+}
+
+#[test]
+fn session_keys_are_tracked_by_session_handlers() {
+    new_test_ext(vec![], None).execute_with(|| {
+        let validators = [1u64, 2];
+        let keys = |offset| {
+            validators
+                .iter()
+                .map(move |validator| (validator, UintAuthorityId(validator + offset)))
+        };
+
+        Singleton::on_genesis_session(keys(10));
+        FinalityKeys::<Test>::on_genesis_session(keys(20));
+
+        assert_eq!(
+            Singleton::authorities(),
+            vec![UintAuthorityId(11), UintAuthorityId(12)]
+        );
+        assert_eq!(Singleton::finality_authority(), Some(UintAuthorityId(21)));
+        assert_eq!(FinalityKeys::<Test>::get(), Some(1));
+        assert_eq!(Singleton::next_authorities(), Singleton::authorities());
+        assert_eq!(
+            Singleton::next_finality_authority(),
+            Singleton::finality_authority()
+        );
+
+        // keys are only updated when they changed, but the queued ones always are.
+        Singleton::on_new_session(false, keys(30), keys(30));
+        FinalityKeys::<Test>::on_new_session(false, keys(40), keys(40));
+
+        assert_eq!(
+            Singleton::authorities(),
+            vec![UintAuthorityId(11), UintAuthorityId(12)]
+        );
+        assert_eq!(Singleton::finality_authority(), Some(UintAuthorityId(21)));
+        assert_eq!(
+            Singleton::next_authorities(),
+            vec![UintAuthorityId(31), UintAuthorityId(32)]
+        );
+        assert_eq!(
+            Singleton::next_finality_authority(),
+            Some(UintAuthorityId(41))
+        );
+
+        Singleton::on_new_session(true, keys(30), keys(30));
+        FinalityKeys::<Test>::on_new_session(true, keys(40), keys(40));
+
+        assert_eq!(
+            Singleton::authorities(),
+            vec![UintAuthorityId(31), UintAuthorityId(32)]
+        );
+        assert_eq!(Singleton::finality_authority(), Some(UintAuthorityId(41)));
+        assert_eq!(FinalityKeys::<Test>::get(), Some(1));
+    });
+}
+
+#[test]
+fn vrf_outputs_are_accumulated_into_next_epoch_randomness() {
+    new_test_ext(vec![1], None).execute_with(|| {
+        let keys = || {
+            [1u64]
+                .iter()
+                .map(|validator| (validator, UintAuthorityId(1)))
+        };
+
+        initialize_block(
+            1,
+            Digest {
+                logs: vec![vrf_pre_digest(0, Some(1))],
+            },
+        );
+        Singleton::on_initialize(1);
+
+        let next_randomness = Singleton::next_randomness();
+        assert_ne!(next_randomness, [0; 32]);
+        assert_eq!(Singleton::randomness(), [0; 32]);
+
+        // blocks without a VRF output don't change it.
+        initialize_block(
+            2,
+            Digest {
+                logs: vec![pre_digest(0)],
+            },
+        );
+        Singleton::on_initialize(2);
+        assert_eq!(Singleton::next_randomness(), next_randomness);
+
+        // and it becomes the epoch randomness at the next session.
+        Singleton::on_new_session(false, keys(), keys());
+        assert_eq!(Singleton::randomness(), next_randomness);
+
+        initialize_block(
+            3,
+            Digest {
+                logs: vec![vrf_pre_digest(0, Some(2))],
+            },
+        );
+        Singleton::on_initialize(3);
+        assert_ne!(Singleton::next_randomness(), next_randomness);
+        assert_eq!(Singleton::randomness(), next_randomness);
+    });
+}
+
+#[test]
+fn timestamp_must_match_pre_runtime_digest() {
+    let timestamped = |engine_id, pre_digest: Vec<u8>| Digest {
+        logs: vec![DigestItem::PreRuntime(engine_id, pre_digest)],
+    };
+    let sealed = PreDigest {
+        authority_index: 0,
+        timestamp: 1_000,
+        vrf: None,
+    };
+    let mined = PowPreDigest { timestamp: 2_000 };
+
+    new_test_ext(vec![1], None).execute_with(|| {
+        initialize_block(1, timestamped(SINGLETON_ENGINE_ID, sealed.encode()));
+        Singleton::on_timestamp_set(1_000u64);
+
+        initialize_block(2, timestamped(SINGLETON_POW_ENGINE_ID, mined.encode()));
+        Singleton::on_timestamp_set(2_000u64);
+
+        // blocks of other engines are left to them.
+        initialize_block(3, Digest::default());
+        Singleton::on_timestamp_set(3_000u64);
+    });
+}
+
+#[test]
+#[should_panic(expected = "Timestamp inherent must match the singleton pre-runtime digest")]
+fn timestamp_ahead_of_pre_runtime_digest_is_rejected() {
+    new_test_ext(vec![1], None).execute_with(|| {
+        initialize_block(
+            1,
+            Digest {
+                logs: vec![pre_digest(0)],
+            },
+        );
+
+        // `pre_digest` is timestamped at zero.
+        Singleton::on_timestamp_set(1u64);
+    });
+}
+
+/// Initializes block 5, whose ancestors 1 to 4 have known hashes.
+fn initialize_block_with_ancestors() {
+    initialize_block(5, Digest::default());
+
+    for ancestor in 1..5 {
+        frame_system::BlockHash::<Test>::insert(ancestor, H256::repeat_byte(ancestor as u8));
+    }
+}
+
+/// Justification of the given block, signed by the given authority.
+fn justification(number: u64, signer: u64) -> Vec<u8> {
+    let hash = System::block_hash(number);
+    TestSignature(signer, hash.as_ref().to_vec()).encode()
+}
+
+#[test]
+fn finality_is_noted_with_valid_justification() {
+    new_test_ext(vec![1], Some(3)).execute_with(|| {
+        initialize_block_with_ancestors();
+
+        assert_ok!(Singleton::note_finality(
+            Origin::none(),
+            2,
+            justification(2, 3)
+        ));
+        assert_eq!(Singleton::last_finalized(), 2);
+
+        assert_ok!(Singleton::note_finality(
+            Origin::none(),
+            4,
+            justification(4, 3)
+        ));
+        assert_eq!(Singleton::last_finalized(), 4);
+        assert_eq!(finalized(), vec![2, 4]);
+    });
+}
+
+#[test]
+fn finality_is_not_noted_with_invalid_justification() {
+    new_test_ext(vec![1], Some(3)).execute_with(|| {
+        initialize_block_with_ancestors();
+
+        // signed by someone else than the finality authority.
+        assert_noop!(
+            Singleton::note_finality(Origin::none(), 2, justification(2, 1)),
+            Error::<Test>::BadJustification,
+        );
+        assert_noop!(
+            Singleton::note_finality(Origin::none(), 2, vec![1, 2, 3]),
+            Error::<Test>::BadJustification,
+        );
+
+        // the current block and unknown blocks can't be finalized.
+        assert_noop!(
+            Singleton::note_finality(Origin::none(), 5, justification(5, 3)),
+            Error::<Test>::UnknownBlock,
+        );
+        assert_noop!(
+            Singleton::note_finality(Origin::none(), 0, justification(0, 3)),
+            Error::<Test>::StaleFinality,
+        );
+
+        assert_ok!(Singleton::note_finality(
+            Origin::none(),
+            3,
+            justification(3, 3)
+        ));
+        assert_noop!(
+            Singleton::note_finality(Origin::none(), 2, justification(2, 3)),
+            Error::<Test>::StaleFinality,
+        );
+        assert_eq!(finalized(), vec![3]);
+    });
+}
+
+#[test]
+fn finality_inherent_is_only_created_for_new_valid_justifications() {
+    new_test_ext(vec![1], Some(3)).execute_with(|| {
+        initialize_block_with_ancestors();
+
+        let inherent_data = |number: u64, justification: Vec<u8>| {
+            let mut inherent_data = InherentData::new();
+            inherent_data
+                .put_data(FINALITY_INHERENT_IDENTIFIER, &(number, justification))
+                .unwrap();
+            inherent_data
+        };
+
+        assert_eq!(Singleton::create_inherent(&InherentData::new()), None);
+        assert_eq!(
+            Singleton::create_inherent(&inherent_data(2, justification(2, 1))),
+            None
+        );
+        assert_eq!(
+            Singleton::create_inherent(&inherent_data(2, justification(2, 3))),
+            Some(Call::note_finality(2, justification(2, 3)))
+        );
+
+        assert_ok!(Singleton::note_finality(
+            Origin::none(),
+            2,
+            justification(2, 3)
+        ));
+        assert_eq!(
+            Singleton::create_inherent(&inherent_data(2, justification(2, 3))),
+            None
+        );
+    });
+}
@@ -6,15 +6,20 @@
 #[cfg(feature = "std")]
 include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
+use grandpa::fg_primitives;
+use grandpa::{AuthorityId as GrandpaId, AuthorityList as GrandpaAuthorityList};
 use sp_api::impl_runtime_apis;
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::OpaqueMetadata;
 use sp_runtime::traits::{
-    BlakeTwo256, Block as BlockT, IdentifyAccount, IdentityLookup, Saturating, Verify,
+    BlakeTwo256, Block as BlockT, IdentifyAccount, IdentityLookup, NumberFor, Saturating, Verify,
 };
 use sp_runtime::{
-    create_runtime_str, generic,
+    app_crypto::AppPublic,
+    create_runtime_str, generic, impl_opaque_keys,
+    traits::{ConvertInto, OpaqueKeys},
     transaction_validity::{TransactionSource, TransactionValidity},
-    ApplyExtrinsicResult, MultiSignature,
+    ApplyExtrinsicResult, ConsensusEngineId, KeyTypeId, MultiSignature,
 };
 use sp_std::prelude::*;
 #[cfg(feature = "std")]
@@ -25,7 +30,7 @@ use sp_version::RuntimeVersion;
 pub use balances::Call as BalancesCall;
 pub use frame_support::{
     construct_runtime, parameter_types,
-    traits::{KeyOwnerProofSystem, Randomness},
+    traits::{FindAuthor, Get, KeyOwnerProofSystem, Randomness},
     weights::{
         constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
         IdentityFee, Weight,
@@ -80,23 +85,38 @@ pub mod opaque {
     pub type Block = generic::Block<Header, UncheckedExtrinsic>;
     /// Opaque block identifier type.
     pub type BlockId = generic::BlockId<Block>;
+
+    impl_opaque_keys! {
+        pub struct SessionKeys {
+            pub singleton: Singleton,
+            pub singleton_finality: SingletonFinality,
+            pub aura: Aura,
+            pub grandpa: Grandpa,
+        }
+    }
 }
 
 pub const VERSION: RuntimeVersion = RuntimeVersion {
     spec_name: create_runtime_str!("node-template"),
     impl_name: create_runtime_str!("node-template"),
     authoring_version: 1,
-    spec_version: 1,
+    spec_version: 15,
     impl_version: 1,
     apis: RUNTIME_API_VERSIONS,
-    transaction_version: 1,
+    transaction_version: 2,
 };
 
-pub const MILLISECS_PER_BLOCK: u64 = 6000;
+/// The signature scheme used by the singleton consensus engine for block seals and finality
+/// justifications. The node must be built with a matching key pair type.
+pub use consensus_primitives::sr25519 as singleton_crypto;
 
-pub const SLOT_DURATION: u64 = MILLISECS_PER_BLOCK;
+/// The singleton block time of the development networks. Each network sets its own in the
+/// genesis config of the singleton pallet, which is also used as the Aura slot duration so that
+/// blocks are authored at the same rate with either engine, and so that singleton blocks never
+/// share an Aura slot.
+pub const MILLISECS_PER_BLOCK: u64 = consensus_primitives::DEFAULT_BLOCK_TIME;
 
-// Time is measured by number of blocks.
+// Time is measured by number of blocks, of the default block time.
 pub const MINUTES: BlockNumber = 60_000 / (MILLISECS_PER_BLOCK as BlockNumber);
 pub const HOURS: BlockNumber = MINUTES * 60;
 pub const DAYS: BlockNumber = HOURS * 24;
@@ -112,7 +132,7 @@ pub fn native_version() -> NativeVersion {
 
 parameter_types! {
     pub const BlockHashCount: BlockNumber = 2400;
-    /// We allow for 2 seconds of compute with a 6 second average block time.
+    /// We allow for 2 seconds of compute with a 10 second average block time.
     pub const MaximumBlockWeight: Weight = 2 * WEIGHT_PER_SECOND;
     pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
     /// Assume 10% of weight for average on_initialize calls.
@@ -183,8 +203,27 @@ impl system::Trait for Runtime {
     type SystemWeightInfo = ();
 }
 
-parameter_types! {
-    pub const MinimumPeriod: u64 = SLOT_DURATION / 2;
+impl aura::Trait for Runtime {
+    type AuthorityId = AuraId;
+}
+
+/// Half the block time set at genesis, which makes it the Aura slot duration.
+pub struct MinimumPeriod;
+
+impl Get<u64> for MinimumPeriod {
+    fn get() -> u64 {
+        Singleton::block_time() / 2
+    }
+}
+
+impl timestamp::Trait for Runtime {
+    /// A timestamp: milliseconds since the unix epoch.
+    type Moment = u64;
+    /// Aura checks the slot of Aura blocks, the singleton pallet that the timestamp of singleton
+    /// blocks matches their pre-runtime digest.
+    type OnTimestampSet = (Aura, Singleton);
+    type MinimumPeriod = MinimumPeriod;
+    type WeightInfo = ();
 }
 
 parameter_types! {
@@ -219,6 +258,90 @@ impl sudo::Trait for Runtime {
     type Call = Call;
 }
 
+impl singleton::Trait for Runtime {
+    type AuthorityId = singleton_crypto::AuthorityId;
+    type FinalityId = singleton_crypto::FinalityId;
+    type OnFinality = Rewards;
+}
+
+impl grandpa::Trait for Runtime {
+    type Event = Event;
+    type Call = Call;
+
+    type KeyOwnerProofSystem = ();
+
+    type KeyOwnerProof =
+        <Self::KeyOwnerProofSystem as KeyOwnerProofSystem<(KeyTypeId, GrandpaId)>>::Proof;
+
+    type KeyOwnerIdentification = <Self::KeyOwnerProofSystem as KeyOwnerProofSystem<(
+        KeyTypeId,
+        GrandpaId,
+    )>>::IdentificationTuple;
+
+    type HandleEquivocation = ();
+}
+
+/// Session handler for the singleton finality authority keys.
+pub type SingletonFinality = singleton::FinalityKeys<Runtime>;
+
+parameter_types! {
+    pub const SessionPeriod: BlockNumber = HOURS;
+    pub const SessionOffset: BlockNumber = 0;
+    pub const DisabledValidatorsThreshold: Perbill = Perbill::from_percent(17);
+}
+
+impl session::Trait for Runtime {
+    type Event = Event;
+    type ValidatorId = AccountId;
+    type ValidatorIdOf = ConvertInto;
+    type ShouldEndSession = session::PeriodicSessions<SessionPeriod, SessionOffset>;
+    type NextSessionRotation = session::PeriodicSessions<SessionPeriod, SessionOffset>;
+    /// The validators never change, but they can rotate their keys with `set_keys`.
+    type SessionManager = ();
+    type SessionHandler = <opaque::SessionKeys as OpaqueKeys>::KeyTypeIdProviders;
+    type Keys = opaque::SessionKeys;
+    type DisabledValidatorsThreshold = DisabledValidatorsThreshold;
+    type WeightInfo = ();
+}
+
+parameter_types! {
+    pub const UncleGenerations: BlockNumber = 0;
+}
+
+/// Finds the index of the block author among the session validators from the singleton
+/// pre-runtime digest, or from the Aura one for blocks authored with Aura.
+pub struct FindBlockAuthor;
+
+impl FindAuthor<u32> for FindBlockAuthor {
+    fn find_author<'a, I>(digests: I) -> Option<u32>
+    where
+        I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+    {
+        let digests = digests.into_iter().collect::<Vec<_>>();
+        Singleton::find_author(digests.iter().cloned()).or_else(|| Aura::find_author(digests))
+    }
+}
+
+impl authorship::Trait for Runtime {
+    type FindAuthor = session::FindAccountFromAuthorIndex<Self, FindBlockAuthor>;
+    type UncleGenerations = UncleGenerations;
+    type FilterUncle = ();
+    type EventHandler = Rewards;
+}
+
+parameter_types! {
+    /// Singleton finality normally lags a few blocks behind, and GRANDPA doesn't justify blocks
+    /// to the runtime at all.
+    pub const MaxUnpaidFinalityRewards: u32 = 10 * MINUTES;
+}
+
+impl rewards::Trait for Runtime {
+    type Currency = Balances;
+    type Event = Event;
+    type FinalityAuthority = SingletonFinality;
+    type MaxUnpaidFinalityRewards = MaxUnpaidFinalityRewards;
+}
+
 /// Configure the pallet template in pallets/template.
 impl template::Trait for Runtime {
     type Event = Event;
@@ -233,9 +356,20 @@ construct_runtime!(
     {
         System: system::{Module, Call, Config, Storage, Event<T>},
         RandomnessCollectiveFlip: randomness_collective_flip::{Module, Call, Storage},
+        Timestamp: timestamp::{Module, Call, Storage, Inherent},
         Balances: balances::{Module, Call, Storage, Config<T>, Event<T>},
         TransactionPayment: transaction_payment::{Module, Storage},
         Sudo: sudo::{Module, Call, Config<T>, Storage, Event<T>},
+        Session: session::{Module, Call, Storage, Event, Config<T>},
+        Singleton: singleton::{Module, Call, Config<T>, Storage, Inherent},
+        // like GRANDPA, Aura is part of the runtime so that the node can author blocks with it
+        // instead of the singleton block author without a runtime upgrade.
+        Aura: aura::{Module, Config<T>, Inherent(Timestamp)},
+        // GRANDPA authorities are tracked even when the node uses the singleton finality gadget,
+        // so that the gadget can be switched without a runtime upgrade.
+        Grandpa: grandpa::{Module, Call, Storage, Config, Event},
+        Authorship: authorship::{Module, Call, Storage},
+        Rewards: rewards::{Module, Config<T>, Storage, Event<T>},
         // Include the custom logic from the template pallet in the runtime.
         TemplateModule: template::{Module, Call, Storage, Event<T>},
     }
@@ -325,13 +459,91 @@ impl_runtime_apis! {
     }
 
     impl sp_session::SessionKeys<Block> for Runtime {
-        fn generate_session_keys(_seed: Option<Vec<u8>>) -> Vec<u8> {
-            vec![]
+        fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
+            opaque::SessionKeys::generate(seed)
         }
 
         fn decode_session_keys(
-            _encoded: Vec<u8>,
-        ) -> Option<Vec<(Vec<u8>, sp_runtime::KeyTypeId)>> {
+            encoded: Vec<u8>,
+        ) -> Option<Vec<(Vec<u8>, KeyTypeId)>> {
+            opaque::SessionKeys::decode_into_raw_public_keys(&encoded)
+        }
+    }
+
+    impl consensus_primitives::SingletonApi<Block, singleton_crypto::AuthorityId> for Runtime {
+        fn signature_scheme() -> consensus_primitives::SignatureScheme {
+            singleton_crypto::SIGNATURE_SCHEME
+        }
+
+        fn leader_election() -> consensus_primitives::LeaderElection {
+            Singleton::leader_election()
+        }
+
+        fn block_time() -> u64 {
+            Singleton::block_time()
+        }
+
+        fn epoch_randomness() -> consensus_primitives::Randomness {
+            Singleton::randomness()
+        }
+
+        fn pow_difficulty() -> Option<u128> {
+            Singleton::pow_difficulty()
+        }
+
+        fn authorities() -> Vec<singleton_crypto::AuthorityId> {
+            Singleton::authorities()
+        }
+
+        fn finality_authority() -> Option<singleton_crypto::AuthorityId> {
+            Singleton::finality_authority().map(|id| {
+                let generic: <singleton_crypto::FinalityId as AppPublic>::Generic = id.into();
+                generic.into()
+            })
+        }
+
+        fn next_authorities() -> Vec<singleton_crypto::AuthorityId> {
+            Singleton::next_authorities()
+        }
+
+        fn next_finality_authority() -> Option<singleton_crypto::AuthorityId> {
+            Singleton::next_finality_authority().map(|id| {
+                let generic: <singleton_crypto::FinalityId as AppPublic>::Generic = id.into();
+                generic.into()
+            })
+        }
+    }
+
+    impl sp_consensus_aura::AuraApi<Block, AuraId> for Runtime {
+        fn slot_duration() -> u64 {
+            Aura::slot_duration()
+        }
+
+        fn authorities() -> Vec<AuraId> {
+            Aura::authorities()
+        }
+    }
+
+    impl fg_primitives::GrandpaApi<Block> for Runtime {
+        fn grandpa_authorities() -> GrandpaAuthorityList {
+            Grandpa::grandpa_authorities()
+        }
+
+        fn submit_report_equivocation_extrinsic(
+            _equivocation_proof: fg_primitives::EquivocationProof<
+                <Block as BlockT>::Hash,
+                NumberFor<Block>,
+            >,
+            _key_owner_proof: fg_primitives::OpaqueKeyOwnershipProof,
+        ) -> Option<()> {
+            None
+        }
+
+        fn generate_key_ownership_proof(
+            _set_id: fg_primitives::SetId,
+            _authority_id: GrandpaId,
+        ) -> Option<fg_primitives::OpaqueKeyOwnershipProof> {
+            // equivocations aren't reported, so the key owner proof type has no values.
             None
         }
     }